@@ -4,14 +4,14 @@ use std::{
     time::Duration,
 };
 
-use log::{debug, trace};
-use serde::de::DeserializeOwned;
+use log::{debug, trace, warn};
+use serde::{de::DeserializeOwned, Serialize};
 use serde_json::{from_str, json};
 use ureq::{Agent, Request};
 use url::Url;
 
 use crate::{
-    model::{Config, Connections, Delay, Log, Proxies, Proxy, Rules, Traffic, Version},
+    model::{Config, Connections, Delay, Level, Log, Proxies, Proxy, Rules, Traffic, Version},
     Error, Result,
 };
 
@@ -224,7 +224,24 @@ impl Clash {
     pub fn reload_configs(&self, force: bool, path: &str) -> Result<()> {
         let body = json!({ "path": path }).to_string();
         debug!("{}", body);
-        self.put::<String>(if force { "configs?force" } else { "configs" }, Some(body))
+        // Clash responds with an empty body, so don't try to parse it
+        self.oneshot_req_with_body(
+            if force { "configs?force" } else { "configs" },
+            "PUT",
+            Some(body),
+        )
+        .map(|_| ())
+    }
+
+    /// Change base configs at runtime, without reloading the profile.
+    ///
+    /// `patch` should serialize into an object of the fields to be changed,
+    /// e.g. `{ "mode": "global" }`
+    pub fn patch_configs<T: Serialize + ?Sized>(&self, patch: &T) -> Result<()> {
+        let body = serde_json::to_string(patch).map_err(|e| Error::other(e.to_string()))?;
+        debug!("{}", body);
+        // Clash responds with an empty body, so don't try to parse it
+        self.oneshot_req_with_body("configs", "PATCH", Some(body))
             .map(|_| ())
     }
 
@@ -282,6 +299,26 @@ impl Clash {
         self.longhaul_req("logs", "GET")
     }
 
+    /// Get real-time logs of `level` and above. Clash only streams `info` and
+    /// above with [`get_log`].
+    ///
+    /// **Note**: This is a longhaul request, which will last forever until
+    /// interrupted or disconnected.
+    ///
+    /// See [`longhaul_req`] for more information
+    ///
+    /// [`get_log`]: Clash::get_log
+    /// [`longhaul_req`]: Clash::longhaul_req
+    pub fn get_log_with_level(&self, level: &Level) -> Result<LongHaul<Log>> {
+        let level = match level {
+            Level::Error => "error",
+            Level::Warning => "warning",
+            Level::Info => "info",
+            Level::Debug => "debug",
+        };
+        self.longhaul_req(&format!("logs?level={}", level), "GET")
+    }
+
     /// Get specific proxy delay test information
     pub fn get_proxy_delay(&self, proxy: &str, test_url: &str, timeout: u64) -> Result<Delay> {
         use urlencoding::encode as e;
@@ -298,6 +335,71 @@ impl Clash {
         self.oneshot_req_with_body(&format!("proxies/{}", group), "PUT", Some(body))?;
         Ok(())
     }
+
+    /// Select proxies in several groups, as `(group, proxy)` pairs, in order.
+    ///
+    /// Each selection is verified by reading the group back. If any of them
+    /// fails, groups changed so far are restored to their previous
+    /// selections, and [`ErrorKind::SelectionFailed`] is returned.
+    ///
+    /// [`ErrorKind::SelectionFailed`]: crate::ErrorKind::SelectionFailed
+    pub fn apply_selections<G: AsRef<str>, P: AsRef<str>>(
+        &self,
+        selections: &[(G, P)],
+    ) -> Result<()> {
+        let mut applied: Vec<(&str, String)> = vec![];
+        for (group, proxy) in selections {
+            let (group, proxy) = (group.as_ref(), proxy.as_ref());
+            if let Err(reason) = self.select_verified(group, proxy, &mut applied) {
+                let unrestored = applied
+                    .iter()
+                    .rev()
+                    .filter_map(|(group, previous)| {
+                        debug!("Restoring {} in {}", previous, group);
+                        self.select_verified(group, previous, &mut vec![])
+                            .map_err(|e| warn!("Cannot restore {} in {} ({})", previous, group, e))
+                            .err()
+                            .map(|_| group.to_string())
+                    })
+                    .collect();
+                return Err(Error::selection_failed(group, proxy, reason, unrestored));
+            }
+        }
+        Ok(())
+    }
+
+    /// Select `proxy` in `group` and read it back. The previous selection is
+    /// pushed to `applied` once the group is changed. Errors are described as
+    /// the reason of [`ErrorKind::SelectionFailed`].
+    ///
+    /// [`ErrorKind::SelectionFailed`]: crate::ErrorKind::SelectionFailed
+    fn select_verified<'a>(
+        &self,
+        group: &'a str,
+        proxy: &str,
+        applied: &mut Vec<(&'a str, String)>,
+    ) -> std::result::Result<(), String> {
+        let now = |clash: &Self| {
+            clash
+                .get_proxy(group)
+                .map(|x| x.now)
+                .map_err(|e| e.to_string())
+        };
+        let previous = now(self)?;
+        self.set_proxygroup_selected(group, proxy)
+            .map_err(|e| e.to_string())?;
+        if let Some(previous) = previous {
+            applied.push((group, previous));
+        }
+        match now(self)? {
+            Some(ref now) if now == proxy => Ok(()),
+            now => Err(format!(
+                "{} is now {}",
+                group,
+                now.as_deref().unwrap_or("unknown")
+            )),
+        }
+    }
 }
 
 pub struct LongHaul<T: DeserializeOwned> {
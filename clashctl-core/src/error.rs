@@ -15,6 +15,22 @@ pub enum ErrorKind {
     #[error("Failed response from server (Code {0})")]
     FailedResponse(u16),
 
+    #[error(
+        "Cannot select {proxy} in {group} ({reason}), {}",
+        if unrestored.is_empty() {
+            "previous selections are restored".to_owned()
+        } else {
+            format!("and failed to restore {}", unrestored.join(", "))
+        }
+    )]
+    SelectionFailed {
+        group: String,
+        proxy: String,
+        reason: String,
+        /// Groups left with the new selection
+        unrestored: Vec<String>,
+    },
+
     #[error("Other errors ({0})")]
     Other(String),
 }
@@ -36,9 +52,33 @@ impl Error {
         Error(Box::new(ErrorKind::BadResponseEncoding))
     }
 
+    pub fn selection_failed(
+        group: &str,
+        proxy: &str,
+        reason: String,
+        unrestored: Vec<String>,
+    ) -> Self {
+        Error(Box::new(ErrorKind::SelectionFailed {
+            group: group.to_owned(),
+            proxy: proxy.to_owned(),
+            reason,
+            unrestored,
+        }))
+    }
+
     pub fn other(msg: String) -> Self {
         Error(Box::new(ErrorKind::Other(msg)))
     }
+
+    /// HTTP status code, if the server responded with an error. E.g. `401`
+    /// means the secret is wrong.
+    pub fn status(&self) -> Option<u16> {
+        match *self.0 {
+            ErrorKind::FailedResponse(code) => Some(code),
+            ErrorKind::RequestError(ureq::Error::Status(code, _)) => Some(code),
+            _ => None,
+        }
+    }
 }
 
 impl<E> From<E> for Error
@@ -1,6 +1,6 @@
 #![doc = include_str!("../README.md")]
 
-mod_use::mod_use![api, error];
+mod_use::mod_use![api, error, profile];
 
 #[cfg(test)]
 mod test;
@@ -1,6 +1,8 @@
+use std::net::IpAddr;
+
 use serde::{Deserialize, Serialize};
 
-use crate::model::{RuleType, TimeType};
+use crate::model::{Rule, RuleType, TimeType};
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 #[serde(rename_all = "camelCase")]
@@ -19,6 +21,85 @@ pub struct Metadata {
     pub network: String,
 }
 
+impl Metadata {
+    /// Generate a rule that matches this connection, routed to `proxy`.
+    ///
+    /// - `Domain` uses the full host
+    /// - `DomainSuffix` uses the registrable domain of the host, e.g.
+    ///   `example.com` for `www.example.com`
+    /// - `DomainKeyword` uses the label before the top-level domain, e.g.
+    ///   `example`
+    /// - `IPCIDR` uses the destination IP, masked to `prefix` bits (default to
+    ///   a single address)
+    ///
+    /// Without a public suffix list, the registrable domain is only guessed
+    /// under generic top-level domains. Under country codes like `co.uk` or
+    /// `github.io` it can't be told apart from the suffix, so the full host is
+    /// used instead, as a rule too narrow is better than one matching other
+    /// sites.
+    ///
+    /// Returns `None` for other rule types, or when the connection lacks the
+    /// host or IP required.
+    pub fn to_rule(&self, rule_type: RuleType, proxy: &str, prefix: Option<u8>) -> Option<Rule> {
+        let host = self.host.trim_end_matches('.');
+        let is_domain = !host.is_empty() && host.parse::<IpAddr>().is_err();
+        let registrable = registrable_domain(host);
+
+        let payload = match rule_type {
+            RuleType::Domain if is_domain => host.to_owned(),
+            RuleType::DomainSuffix if is_domain => registrable.unwrap_or(host).to_owned(),
+            RuleType::DomainKeyword if is_domain => registrable
+                .and_then(|x| x.split('.').next())
+                .unwrap_or(host)
+                .to_owned(),
+            RuleType::IPCIDR => {
+                let ip = self.destination_ip.parse::<IpAddr>().ok()?;
+                cidr(ip, prefix)
+            }
+            _ => return None,
+        };
+
+        Some(Rule {
+            rule_type,
+            payload,
+            proxy: proxy.to_owned(),
+        })
+    }
+}
+
+/// Last two labels of `host`, when its top-level domain is generic, e.g.
+/// `example.com` for `www.example.com`. Two-letter country code domains often
+/// have multi-label suffixes (`co.uk`, `com.cn`, `github.io`), so `None` is
+/// returned for them unless the host has only two labels.
+fn registrable_domain(host: &str) -> Option<&str> {
+    let mut dots = host.rmatch_indices('.').map(|(i, _)| i);
+    let tld_start = dots.next()? + 1;
+    let is_cc_tld = host.len() - tld_start == 2;
+    match dots.next() {
+        Some(i) if !is_cc_tld => Some(&host[i + 1..]),
+        Some(_) => None,
+        None if tld_start > 1 => Some(host),
+        None => None,
+    }
+}
+
+fn cidr(ip: IpAddr, prefix: Option<u8>) -> String {
+    match ip {
+        IpAddr::V4(ip) => {
+            let prefix = prefix.unwrap_or(32).min(32);
+            let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+            let net = std::net::Ipv4Addr::from(u32::from(ip) & mask);
+            format!("{}/{}", net, prefix)
+        }
+        IpAddr::V6(ip) => {
+            let prefix = prefix.unwrap_or(128).min(128);
+            let mask = u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0);
+            let net = std::net::Ipv6Addr::from(u128::from(ip) & mask);
+            format!("{}/{}", net, prefix)
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 #[serde(rename_all = "camelCase")]
 pub struct Connection {
@@ -128,3 +209,89 @@ mod deserialize {
         }
     }
 }
+
+#[test]
+fn test_metadata_to_rule() {
+    let meta = Metadata {
+        connection_type: "HTTP".into(),
+        source_ip: "192.168.1.2".into(),
+        source_port: "51234".into(),
+        destination_ip: "1.2.3.4".into(),
+        destination_port: "443".into(),
+        host: "www.example.com".into(),
+        network: "tcp".into(),
+    };
+    let line = |rule_type, prefix| {
+        meta.to_rule(rule_type, "REJECT", prefix)
+            .and_then(|x| x.to_profile_line())
+    };
+    assert_eq!(
+        line(RuleType::DomainSuffix, None).as_deref(),
+        Some("DOMAIN-SUFFIX,example.com,REJECT")
+    );
+    assert_eq!(
+        line(RuleType::Domain, None).as_deref(),
+        Some("DOMAIN,www.example.com,REJECT")
+    );
+    assert_eq!(
+        line(RuleType::DomainKeyword, None).as_deref(),
+        Some("DOMAIN-KEYWORD,example,REJECT")
+    );
+    assert_eq!(
+        line(RuleType::IPCIDR, Some(24)).as_deref(),
+        Some("IP-CIDR,1.2.3.0/24,REJECT")
+    );
+    assert_eq!(
+        line(RuleType::IPCIDR, None).as_deref(),
+        Some("IP-CIDR,1.2.3.4/32,REJECT")
+    );
+    assert_eq!(line(RuleType::GeoIP, None), None);
+
+    // Multi-label suffixes can't be told from registrable domains, so the full
+    // host is used
+    let rule = |host: &str, rule_type| {
+        Metadata {
+            host: host.into(),
+            ..meta.clone()
+        }
+        .to_rule(rule_type, "DIRECT", None)
+        .map(|x| x.payload)
+    };
+    assert_eq!(
+        rule("www.foo.co.uk", RuleType::DomainSuffix).as_deref(),
+        Some("www.foo.co.uk")
+    );
+    assert_eq!(
+        rule("www.foo.co.uk", RuleType::DomainKeyword).as_deref(),
+        Some("www.foo.co.uk")
+    );
+    assert_eq!(
+        rule("user.github.io", RuleType::DomainSuffix).as_deref(),
+        Some("user.github.io")
+    );
+    assert_eq!(
+        rule("example.de.", RuleType::DomainSuffix).as_deref(),
+        Some("example.de")
+    );
+    assert_eq!(
+        rule("example.de", RuleType::DomainKeyword).as_deref(),
+        Some("example")
+    );
+    assert_eq!(
+        rule("a.b.example.org", RuleType::DomainSuffix).as_deref(),
+        Some("example.org")
+    );
+    assert_eq!(
+        rule("localhost", RuleType::DomainSuffix).as_deref(),
+        Some("localhost")
+    );
+
+    let no_host = Metadata {
+        host: String::new(),
+        ..meta
+    };
+    assert_eq!(
+        no_host.to_rule(RuleType::DomainSuffix, "DIRECT", None),
+        None
+    );
+}
@@ -29,9 +29,23 @@ pub struct Delay {
 pub struct Version {
     // Clash Premium only
     pub premium: Option<bool>,
+    // Clash.Meta (mihomo) only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<bool>,
     pub version: VersionPayload,
 }
 
+impl Version {
+    /// Name of the Clash flavor, e.g. `Premium` or `Meta`
+    pub fn flavor(&self) -> &'static str {
+        match (self.premium, self.meta) {
+            (_, Some(true)) => "Meta",
+            (Some(true), _) => "Premium",
+            _ => "Clash",
+        }
+    }
+}
+
 cfg_if! {
     if #[cfg(feature = "deserialize")] {
         use chrono::{Utc, DateTime};
@@ -32,6 +32,25 @@ pub enum RuleType {
     Unknown,
 }
 
+impl RuleType {
+    /// Keyword of this rule type in Clash profiles, e.g. `DOMAIN-SUFFIX`
+    pub fn profile_keyword(&self) -> Option<&'static str> {
+        Some(match self {
+            RuleType::Domain => "DOMAIN",
+            RuleType::DomainSuffix => "DOMAIN-SUFFIX",
+            RuleType::DomainKeyword => "DOMAIN-KEYWORD",
+            RuleType::GeoIP => "GEOIP",
+            RuleType::IPCIDR => "IP-CIDR",
+            RuleType::SrcIPCIDR => "SRC-IP-CIDR",
+            RuleType::SrcPort => "SRC-PORT",
+            RuleType::DstPort => "DST-PORT",
+            RuleType::Process => "PROCESS-NAME",
+            RuleType::Match => "MATCH",
+            RuleType::Direct | RuleType::Reject | RuleType::Unknown => return None,
+        })
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Rule {
     #[serde(rename = "type")]
@@ -40,6 +59,20 @@ pub struct Rule {
     pub proxy: String,
 }
 
+impl Rule {
+    /// Render the rule as it is written in a Clash profile, e.g.
+    /// `DOMAIN-SUFFIX,example.com,REJECT`. Returns `None` for rule types that
+    /// cannot be written into a profile.
+    pub fn to_profile_line(&self) -> Option<String> {
+        let keyword = self.rule_type.profile_keyword()?;
+        Some(if matches!(self.rule_type, RuleType::Match) {
+            format!("{},{}", keyword, self.proxy)
+        } else {
+            format!("{},{},{}", keyword, self.payload, self.proxy)
+        })
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Default, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Rules {
     pub rules: Vec<Rule>,
@@ -63,6 +96,25 @@ impl Rules {
         counts
     }
 
+    /// Number of rules of each target proxy, including `DIRECT` and `REJECT`
+    /// which are left out by [`Rules::frequency`]
+    pub fn target_frequency(&self) -> HashMap<&str, usize> {
+        let mut counts = HashMap::new();
+        self.rules
+            .iter()
+            .for_each(|x| *counts.entry(x.proxy.as_str()).or_default() += 1);
+        counts
+    }
+
+    /// Number of rules of each type
+    pub fn type_frequency(&self) -> HashMap<RuleType, usize> {
+        let mut counts = HashMap::new();
+        self.rules
+            .iter()
+            .for_each(|x| *counts.entry(x.rule_type).or_default() += 1);
+        counts
+    }
+
     pub fn owned_frequency(&self) -> HashMap<String, usize> {
         let mut counts = HashMap::new();
         self.rules
@@ -73,3 +125,25 @@ impl Rules {
         counts
     }
 }
+
+#[test]
+fn test_rules_frequency() {
+    let rule = |rule_type, proxy: &str| Rule {
+        rule_type,
+        payload: String::new(),
+        proxy: proxy.to_owned(),
+    };
+    let rules = Rules {
+        rules: vec![
+            rule(RuleType::DomainSuffix, "Proxy"),
+            rule(RuleType::DomainSuffix, "REJECT"),
+            rule(RuleType::GeoIP, "DIRECT"),
+            rule(RuleType::Match, "Proxy"),
+        ],
+    };
+    assert_eq!(rules.frequency().len(), 1);
+    assert_eq!(rules.target_frequency()["Proxy"], 2);
+    assert_eq!(rules.target_frequency()["REJECT"], 1);
+    assert_eq!(rules.type_frequency()[&RuleType::DomainSuffix], 2);
+    assert_eq!(rules.type_frequency()[&RuleType::Match], 1);
+}
@@ -0,0 +1,384 @@
+use std::{fmt::Display, str::FromStr};
+
+use url::Url;
+
+use crate::{Error, Result};
+
+/// Where a rule should be inserted into the `rules` section of a profile
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RulePosition {
+    /// Before all existing rules
+    #[default]
+    Top,
+    /// After all existing rules, but before the trailing `MATCH` rule if
+    /// there is one
+    Bottom,
+    /// Before the n-th (0-based) existing rule
+    Index(usize),
+}
+
+impl FromStr for RulePosition {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "top" => Ok(Self::Top),
+            "bottom" => Ok(Self::Bottom),
+            other => other.parse().map(Self::Index).map_err(|_| {
+                Error::other(format!(
+                    "Bad rule position `{}`, expect `top`, `bottom` or an index",
+                    s
+                ))
+            }),
+        }
+    }
+}
+
+/// # Clash profile
+///
+/// A Clash profile (the YAML config file loaded by Clash), kept as raw lines
+/// so that rewriting it preserves comments and formatting. Only the parts
+/// clashctl needs are understood: the `rules` section, and top-level scalars
+/// like `external-controller` and `secret`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Profile {
+    lines: Vec<String>,
+}
+
+impl Profile {
+    pub fn parse(content: &str) -> Self {
+        Self {
+            lines: content.lines().map(ToOwned::to_owned).collect(),
+        }
+    }
+
+    /// Value of a top-level scalar key, e.g. `7890` of `port: 7890`. Empty
+    /// values are treated as missing.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.lines.iter().find_map(|line| {
+            let value = strip_comment(line)
+                .strip_prefix(key)?
+                .trim_end()
+                .strip_prefix(':')?;
+            Some(unquote(value.trim())).filter(|x| !x.is_empty())
+        })
+    }
+
+    /// Secret of the API, set by `secret`
+    pub fn secret(&self) -> Option<&str> {
+        self.get("secret")
+    }
+
+    /// URL of the API, set by `external-controller`, or
+    /// `external-controller-tls` of Clash Premium and mihomo.
+    ///
+    /// Addresses to bind are turned into ones to connect to, e.g. `:9090`
+    /// into `http://127.0.0.1:9090`.
+    pub fn external_controller(&self) -> Result<Option<Url>> {
+        let (addr, scheme) = match (
+            self.get("external-controller"),
+            self.get("external-controller-tls"),
+        ) {
+            (Some(addr), _) => (addr, "http"),
+            (None, Some(addr)) => (addr, "https"),
+            (None, None) => return Ok(None),
+        };
+        controller_url(addr, scheme).map(Some)
+    }
+
+    /// Index of the top-level `rules:` line, if present. Only a block
+    /// sequence of rules can be edited line by line, so a flow one like
+    /// `rules: [...]` is refused.
+    fn rules_header(&self) -> Result<Option<usize>> {
+        let header = self.lines.iter().position(|line| {
+            line.strip_prefix("rules")
+                .is_some_and(|x| x.trim_start().starts_with(':'))
+        });
+        let header = match header {
+            Some(header) => header,
+            None => return Ok(None),
+        };
+        let value = self.lines[header]
+            .split_once(':')
+            .map_or("", |(_, value)| value);
+        if !strip_comment(value).trim().is_empty() {
+            return Err(not_block());
+        }
+        Ok(Some(header))
+    }
+
+    /// Line indexes and content of every entry in the `rules` section
+    fn rule_lines(&self) -> Result<Vec<(usize, &str)>> {
+        let header = match self.rules_header()? {
+            Some(header) => header,
+            None => return Ok(vec![]),
+        };
+        let mut ret = vec![];
+        for (index, line) in self.lines.iter().enumerate().skip(header + 1) {
+            let content = strip_comment(line).trim();
+            if content.is_empty() {
+                continue;
+            }
+            // Rules are either an indented or a flush block sequence. Anything else
+            // means the next top-level key has started, unless it's indented, like a
+            // flow sequence on its own line
+            match content.strip_prefix('-') {
+                Some(rule) if line.starts_with([' ', '\t', '-']) => {
+                    ret.push((index, unquote(rule.trim())))
+                }
+                _ if ret.is_empty() && line.starts_with([' ', '\t']) => return Err(not_block()),
+                _ => break,
+            }
+        }
+        Ok(ret)
+    }
+
+    /// Rules in the `rules` section, in order
+    pub fn rules(&self) -> Result<Vec<&str>> {
+        Ok(self
+            .rule_lines()?
+            .into_iter()
+            .map(|(_, rule)| rule)
+            .collect())
+    }
+
+    /// Insert a rule (e.g. `DOMAIN-SUFFIX,example.com,REJECT`) into the
+    /// `rules` section, creating the section when it does not exist.
+    ///
+    /// Returns the index of the inserted rule among all rules. Fails if the
+    /// `rules` section is not a block sequence.
+    pub fn insert_rule(&mut self, rule: &str, position: RulePosition) -> Result<usize> {
+        let is_match = |rule: &str| {
+            rule.split(',')
+                .next()
+                .is_some_and(|x| x.trim().eq_ignore_ascii_case("MATCH"))
+        };
+
+        // (line index, whether it's a `MATCH` rule) of existing rules
+        let rules = self
+            .rule_lines()?
+            .into_iter()
+            .map(|(index, rule)| (index, is_match(rule)))
+            .collect::<Vec<_>>();
+
+        let header = match self.rules_header()? {
+            Some(header) => header,
+            None => {
+                if self.lines.last().is_some_and(|x| !x.trim().is_empty()) {
+                    self.lines.push(String::new());
+                }
+                self.lines.push("rules:".to_owned());
+                self.lines.len() - 1
+            }
+        };
+
+        // Follow the indentation of existing rules
+        let indent = rules
+            .first()
+            .map(|(index, _)| {
+                let line = &self.lines[*index];
+                line[..line.len() - line.trim_start().len()].to_owned()
+            })
+            .unwrap_or_else(|| "  ".to_owned());
+
+        let index = match position {
+            RulePosition::Top => 0,
+            RulePosition::Index(index) => index.min(rules.len()),
+            RulePosition::Bottom => match rules.last() {
+                Some((_, true)) => rules.len() - 1,
+                _ => rules.len(),
+            },
+        };
+
+        let line_index = match rules.get(index) {
+            Some((line_index, _)) => *line_index,
+            None => rules.last().map_or(header, |(line_index, _)| *line_index) + 1,
+        };
+
+        self.lines
+            .insert(line_index, format!("{}- {}", indent, rule));
+        Ok(index)
+    }
+}
+
+impl Display for Profile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for line in &self.lines {
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+/// Turn an address the API binds to into a URL to connect to it
+fn controller_url(addr: &str, scheme: &str) -> Result<Url> {
+    let bad_addr = || Error::other(format!("Bad address of external controller `{}`", addr));
+
+    if addr.contains("://") {
+        return Url::parse(addr).map_err(|_| bad_addr());
+    }
+    let (host, port) = addr.rsplit_once(':').ok_or_else(bad_addr)?;
+    let host = match host {
+        "" | "0.0.0.0" | "*" => "127.0.0.1",
+        "[::]" => "[::1]",
+        host => host,
+    };
+    Url::parse(&format!("{}://{}:{}", scheme, host, port)).map_err(|_| bad_addr())
+}
+
+fn not_block() -> Error {
+    Error::other(
+        "Only rules written as a block sequence (one `- RULE` per line) can be edited".to_owned(),
+    )
+}
+
+/// Part of `line` before its comment. A `#` starts a comment only at the
+/// start of the line or after a space, and never inside quotes, e.g. in
+/// `secret: "abc #1"`.
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut prev = ' ';
+    for (index, ch) in line.char_indices() {
+        match (quote, ch) {
+            (None, '#') if prev.is_whitespace() => return &line[..index],
+            (None, '\'' | '"') => quote = Some(ch),
+            (Some(q), _) if q == ch => quote = None,
+            _ => {}
+        }
+        prev = ch;
+    }
+    line
+}
+
+fn unquote(s: &str) -> &str {
+    s.strip_prefix('\'')
+        .and_then(|x| x.strip_suffix('\''))
+        .or_else(|| s.strip_prefix('"').and_then(|x| x.strip_suffix('"')))
+        .unwrap_or(s)
+}
+
+#[test]
+fn test_insert_rule() {
+    let raw = "\
+port: 7890
+# Rules
+rules:
+  - DOMAIN-SUFFIX,google.com,Proxy # comment
+  - 'GEOIP,CN,DIRECT'
+  - MATCH,Proxy
+dns:
+  enable: true
+";
+    let mut profile = Profile::parse(raw);
+    assert_eq!(
+        profile.rules().unwrap(),
+        vec![
+            "DOMAIN-SUFFIX,google.com,Proxy",
+            "GEOIP,CN,DIRECT",
+            "MATCH,Proxy"
+        ]
+    );
+
+    assert_eq!(
+        profile
+            .insert_rule("DOMAIN,a.com,REJECT", RulePosition::Top)
+            .unwrap(),
+        0
+    );
+    assert_eq!(
+        profile
+            .insert_rule("DOMAIN,b.com,REJECT", RulePosition::Bottom)
+            .unwrap(),
+        3
+    );
+    assert_eq!(
+        profile
+            .insert_rule("DOMAIN,c.com,REJECT", RulePosition::Index(1))
+            .unwrap(),
+        1
+    );
+    assert_eq!(
+        profile.rules().unwrap(),
+        vec![
+            "DOMAIN,a.com,REJECT",
+            "DOMAIN,c.com,REJECT",
+            "DOMAIN-SUFFIX,google.com,Proxy",
+            "GEOIP,CN,DIRECT",
+            "DOMAIN,b.com,REJECT",
+            "MATCH,Proxy"
+        ]
+    );
+    assert!(profile.to_string().ends_with("dns:\n  enable: true\n"));
+
+    let mut empty = Profile::parse("port: 7890\n");
+    empty
+        .insert_rule("DOMAIN,a.com,REJECT", RulePosition::Bottom)
+        .unwrap();
+    assert_eq!(
+        empty.to_string(),
+        "port: 7890\n\nrules:\n  - DOMAIN,a.com,REJECT\n"
+    );
+
+    let mut commented = Profile::parse("rules: # comment\n- MATCH,DIRECT\n");
+    commented
+        .insert_rule("DOMAIN,a.com,REJECT", RulePosition::Bottom)
+        .unwrap();
+    assert_eq!(
+        commented.to_string(),
+        "rules: # comment\n- DOMAIN,a.com,REJECT\n- MATCH,DIRECT\n"
+    );
+
+    for raw in [
+        "rules: [MATCH,DIRECT]\n",
+        "rules : ['GEOIP,CN,DIRECT', MATCH,Proxy] # flow\n",
+        "rules:\n  [MATCH,DIRECT]\n",
+    ] {
+        let mut flow = Profile::parse(raw);
+        assert!(flow.rules().is_err());
+        assert!(flow
+            .insert_rule("DOMAIN,a.com,REJECT", RulePosition::Top)
+            .is_err());
+        assert_eq!(flow.to_string(), raw);
+    }
+}
+
+#[test]
+fn test_external_controller() {
+    let controller = |raw: &str| {
+        Profile::parse(raw)
+            .external_controller()
+            .unwrap()
+            .map(String::from)
+    };
+
+    assert_eq!(
+        controller("port: 7890\nexternal-controller: :9090\n").as_deref(),
+        Some("http://127.0.0.1:9090/")
+    );
+    assert_eq!(
+        controller("external-controller: '0.0.0.0:9090' # all\n").as_deref(),
+        Some("http://127.0.0.1:9090/")
+    );
+    assert_eq!(
+        controller("external-controller: \"[::]:9090\"\n").as_deref(),
+        Some("http://[::1]:9090/")
+    );
+    assert_eq!(
+        controller("external-controller-tls: 192.168.1.1:9443\n").as_deref(),
+        Some("https://192.168.1.1:9443/")
+    );
+    assert_eq!(controller("external-controller: ''\n"), None);
+    assert_eq!(controller("dns:\n  external-controller: :9090\n"), None);
+    assert!(Profile::parse("external-controller: 9090\n")
+        .external_controller()
+        .is_err());
+
+    let profile = Profile::parse("secret: \"abc\" # comment\nsecret-key: x\n");
+    assert_eq!(profile.secret(), Some("abc"));
+    let profile = Profile::parse("secret: \"abc #1\" # comment\n");
+    assert_eq!(profile.secret(), Some("abc #1"));
+    let profile = Profile::parse("secret: 'it''s #1'\n");
+    assert_eq!(profile.secret(), Some("it''s #1"));
+    let profile = Profile::parse("secret: abc#1 # comment\n");
+    assert_eq!(profile.secret(), Some("abc#1"));
+}
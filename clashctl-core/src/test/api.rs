@@ -64,6 +64,32 @@ fn test_set_proxy() {
     }
 }
 
+#[test]
+fn test_apply_selections() {
+    let clash = init();
+    let proxies = clash.get_proxies().unwrap();
+    let (group, proxy) = proxies
+        .iter()
+        .find(|(_, proxy)| proxy.proxy_type.is_selector())
+        .unwrap();
+    let now = proxy.now.clone().unwrap();
+    let other = proxy
+        .all
+        .iter()
+        .flatten()
+        .find(|x| x != &&now)
+        .unwrap_or(&now);
+
+    clash.apply_selections(&[(group, other)]).unwrap();
+    assert_eq!(clash.get_proxy(group).unwrap().now.as_ref(), Some(other));
+
+    // Selections made before the failing one are restored
+    clash
+        .apply_selections(&[(group, &now), (group, &"Not a member".to_owned())])
+        .unwrap_err();
+    assert_eq!(clash.get_proxy(group).unwrap().now.as_ref(), Some(other));
+}
+
 #[test]
 fn test_configs() {
     let clash = init();
@@ -0,0 +1,389 @@
+use std::{thread::sleep, time::Duration};
+
+use chrono::{DateTime, Local};
+use clap::Parser;
+use clashctl_core::{
+    model::{Proxies, ProxyType},
+    strum, Clash,
+};
+use log::{info, warn};
+use owo_colors::OwoColorize;
+use regex::Regex;
+use serde::Serialize;
+
+use crate::{
+    interactive::Flags, match_name, pick_best, DelayStats, DelayTest, DelayTestOpt, Render, Result,
+};
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Serialize,
+    strum::EnumString,
+    strum::Display,
+    strum::EnumVariantNames,
+)]
+#[serde(rename_all = "lowercase")]
+#[strum(ascii_case_insensitive, serialize_all = "lowercase")]
+pub enum AutoPolicy {
+    /// Lowest average delay
+    Latency,
+    /// First available member, in the order of the group
+    Failover,
+    /// Lowest delay among members matching `--pattern`, or among all members
+    /// when none of them is available
+    Pattern,
+}
+
+#[derive(Parser, Debug, Clone)]
+pub struct AutoOpt {
+    #[clap(help = "Selector group to manage, case-insensitive and fuzzy")]
+    pub group: String,
+
+    #[clap(
+        long,
+        default_value = "latency",
+        possible_values = &["latency", "failover", "pattern"],
+        help = "How the best member is chosen"
+    )]
+    pub policy: AutoPolicy,
+
+    #[clap(
+        long,
+        required_if_eq("policy", "pattern"),
+        help = "Preferred member names, as a regex. Used by the `pattern` policy"
+    )]
+    pub pattern: Option<Regex>,
+
+    #[clap(
+        long,
+        default_value = "60",
+        help = "Seconds between two checks of the group"
+    )]
+    pub interval: u64,
+
+    #[clap(
+        long,
+        default_value = "30",
+        help = "Delay in ms that a member has to win by before switching to it"
+    )]
+    pub margin: u64,
+
+    #[clap(
+        long,
+        default_value = "3",
+        help = "Number of consecutive checks a member has to win before switching to it"
+    )]
+    pub consecutive: usize,
+
+    #[clap(flatten)]
+    pub test: DelayTestOpt,
+}
+
+/// Outcome of one check of the group
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "decision", rename_all = "lowercase")]
+pub enum Decision {
+    /// Current member is still the one to use
+    Keep,
+    /// `to` has won `streak` consecutive checks, but not enough to switch yet
+    Pending { to: String, streak: usize },
+    /// Switch to `to`
+    Switch { to: String },
+}
+
+/// Decides which member to use, with hysteresis: a member has to be better
+/// than the current one for [`AutoSwitcher::consecutive`] checks in a row
+/// before the group is switched to it.
+#[derive(Debug, Clone)]
+pub struct AutoSwitcher {
+    pub policy: AutoPolicy,
+    pub pattern: Option<Regex>,
+    pub margin: u64,
+    pub consecutive: usize,
+    challenger: Option<(String, usize)>,
+}
+
+impl AutoSwitcher {
+    pub fn new(opt: &AutoOpt) -> Self {
+        Self {
+            policy: opt.policy,
+            pattern: opt.pattern.clone(),
+            margin: opt.margin,
+            consecutive: opt.consecutive,
+            challenger: None,
+        }
+    }
+
+    /// Member that should replace `current` according to the policy, if any.
+    /// `stats` should be in the order of the group.
+    pub fn challenger<'a>(&self, stats: &'a [DelayStats], current: &str) -> Option<&'a DelayStats> {
+        let current = stats.iter().find(|x| x.name == current);
+        let current_avg = current.and_then(|x| x.avg);
+        // Anything beats a current member that is unavailable, while an
+        // unavailable member never wins
+        let wins_by_margin = |x: &DelayStats| match (x.avg, current_avg) {
+            (_, None) => true,
+            (Some(avg), Some(cur)) => avg.saturating_add(self.margin) < cur,
+            (None, Some(_)) => false,
+        };
+
+        let best = match self.policy {
+            AutoPolicy::Latency => pick_best(stats, None).filter(|x| wins_by_margin(x)),
+            AutoPolicy::Failover => stats.iter().find(|x| x.is_alive()),
+            AutoPolicy::Pattern => {
+                let is_preferred =
+                    |x: &DelayStats| self.pattern.as_ref().is_some_and(|p| p.is_match(&x.name));
+                let preferred = stats
+                    .iter()
+                    .filter(|x| is_preferred(x))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                match pick_best(&preferred, None) {
+                    // Moving to a preferred member does not need to win by margin
+                    Some(best) if !current.is_some_and(|x| is_preferred(x) && x.is_alive()) => {
+                        stats.iter().find(|x| x.name == best.name)
+                    }
+                    Some(best) => stats
+                        .iter()
+                        .find(|x| x.name == best.name)
+                        .filter(|x| wins_by_margin(x)),
+                    None => pick_best(stats, None).filter(|x| wins_by_margin(x)),
+                }
+            }
+        };
+        best.filter(|x| Some(x.name.as_str()) != current.map(|x| x.name.as_str()))
+    }
+
+    /// Record the result of a check and decide what to do
+    pub fn decide(&mut self, stats: &[DelayStats], current: &str) -> Decision {
+        let to = match self.challenger(stats, current) {
+            Some(x) => x.name.to_owned(),
+            None => {
+                self.challenger = None;
+                return Decision::Keep;
+            }
+        };
+        let streak = match self.challenger {
+            Some((ref name, streak)) if name == &to => streak + 1,
+            _ => 1,
+        };
+        if streak >= self.consecutive {
+            self.challenger = None;
+            Decision::Switch { to }
+        } else {
+            self.challenger = Some((to.clone(), streak));
+            Decision::Pending { to, streak }
+        }
+    }
+}
+
+/// One check of the group, printed by `auto`
+#[derive(Serialize, Debug, Clone)]
+pub struct AutoRecord {
+    pub time: DateTime<Local>,
+    pub group: String,
+    pub current: String,
+    /// Average delay of `current`, `None` if it failed or is not tested
+    pub current_delay: Option<u64>,
+    #[serde(flatten)]
+    pub decision: Decision,
+    /// Average delay of the member in decision
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delay: Option<u64>,
+    #[serde(skip)]
+    pub required: usize,
+}
+
+impl Render for AutoRecord {
+    fn render_table(&self) {
+        let ms =
+            |x: Option<u64>| x.map_or_else(|| "unavailable".to_owned(), |x| format!("{} ms", x));
+        let time = self.time.format("%F %T").to_string();
+        let current = format!("{} ({})", self.current, ms(self.current_delay));
+        match self.decision {
+            Decision::Keep => println!("{} {}: keep {}", time.dimmed(), self.group, current),
+            Decision::Pending { ref to, streak } => println!(
+                "{} {}: {} ({}) beats {}, {}/{} checks",
+                time.dimmed(),
+                self.group,
+                to.yellow(),
+                ms(self.delay),
+                current,
+                streak,
+                self.required
+            ),
+            Decision::Switch { ref to } => println!(
+                "{} {}: switch from {} to {} ({})",
+                time.dimmed(),
+                self.group,
+                current,
+                to.green(),
+                ms(self.delay)
+            ),
+        }
+    }
+}
+
+impl AutoOpt {
+    pub fn handle(&self, flags: &Flags) -> Result<()> {
+        let server = match flags.get_server()? {
+            Some(server) => server,
+            None => {
+                warn!("No server configured yet. Use `clashctl server add` first.");
+                return Ok(());
+            }
+        };
+        info!("Using {}", server);
+        server.check_writable()?;
+
+        let config = flags.get_config_or_default();
+        let clash = server
+            .clone()
+            .into_clash_with_timeout(Some(Duration::from_millis(
+                flags.timeout(Some(&server))
+                    + self.test.max_timeout(flags, &server, &config.group_tests),
+            )))?;
+
+        let proxies = clash.get_proxies()?;
+        let group = match_name(
+            "group",
+            &self.group,
+            proxies.selectors().map(|(name, _)| name.as_str()),
+        )?
+        .to_owned();
+        let delay_test = self
+            .test
+            .delay_test(flags, &server, config.group_test(&group));
+        info!(
+            "Managing {} with {} policy, checking every {}s",
+            group.green(),
+            self.policy,
+            self.interval
+        );
+
+        let mut switcher = AutoSwitcher::new(self);
+        loop {
+            if let Err(e) = self.check(&clash, &group, &delay_test, &mut switcher, flags) {
+                warn!("Check failed: {}", e)
+            }
+            sleep(Duration::from_secs(self.interval))
+        }
+    }
+
+    fn check(
+        &self,
+        clash: &Clash,
+        group: &str,
+        delay_test: &DelayTest,
+        switcher: &mut AutoSwitcher,
+        flags: &Flags,
+    ) -> Result<()> {
+        let proxies = clash.get_proxies()?;
+        // Read the selection every time, in case it is changed by others
+        let current = proxies
+            .get(group)
+            .and_then(|x| x.now.to_owned())
+            .unwrap_or_default();
+        let stats = delay_test.run(clash, &members(&proxies, group))?;
+        let decision = switcher.decide(&stats, &current);
+
+        if let Decision::Switch { ref to } = decision {
+            clash.set_proxygroup_selected(group, to)?;
+        }
+
+        let delay_of = |name: &str| stats.iter().find(|x| x.name == name).and_then(|x| x.avg);
+        flags.output.print(&AutoRecord {
+            time: Local::now(),
+            group: group.to_owned(),
+            current_delay: delay_of(&current),
+            delay: match decision {
+                Decision::Pending { ref to, .. } | Decision::Switch { ref to } => delay_of(to),
+                Decision::Keep => None,
+            },
+            current,
+            decision,
+            required: self.consecutive,
+        })
+    }
+}
+
+/// Normal proxies of `group`, in the order of the group
+fn members(proxies: &Proxies, group: &str) -> Vec<(String, ProxyType)> {
+    proxies
+        .get(group)
+        .and_then(|x| x.all.as_ref())
+        .into_iter()
+        .flatten()
+        .filter_map(|name| proxies.get(name).map(|x| (name.to_owned(), x.proxy_type)))
+        .filter(|(_, proxy_type)| proxy_type.is_normal())
+        .collect()
+}
+
+#[test]
+fn test_auto_switcher() {
+    let stats = |delays: &[(&str, Option<u64>)]| {
+        delays
+            .iter()
+            .map(|(name, delay)| DelayStats::from_samples(name, ProxyType::Vmess, &[*delay]))
+            .collect::<Vec<_>>()
+    };
+    let mut switcher = AutoSwitcher {
+        policy: AutoPolicy::Latency,
+        pattern: None,
+        margin: 30,
+        consecutive: 2,
+        challenger: None,
+    };
+
+    // Not winning by margin
+    let round = stats(&[("A", Some(100)), ("B", Some(80))]);
+    assert_eq!(switcher.decide(&round, "A"), Decision::Keep);
+    let round = stats(&[("A", Some(100)), ("B", None)]);
+    assert!(switcher.challenger(&round, "A").is_none());
+
+    // Winning, but a streak of 2 is required
+    let round = stats(&[("A", Some(100)), ("B", Some(60))]);
+    let pending = Decision::Pending {
+        to: "B".to_owned(),
+        streak: 1,
+    };
+    assert_eq!(switcher.decide(&round, "A"), pending);
+
+    // Streak is broken
+    let round = stats(&[("A", Some(100)), ("B", Some(90))]);
+    assert_eq!(switcher.decide(&round, "A"), Decision::Keep);
+    let round = stats(&[("A", Some(100)), ("B", Some(60))]);
+    assert_eq!(switcher.decide(&round, "A"), pending);
+    let switch = Decision::Switch { to: "B".to_owned() };
+    assert_eq!(switcher.decide(&round, "A"), switch);
+
+    // Failover picks the first available member
+    switcher.policy = AutoPolicy::Failover;
+    switcher.consecutive = 1;
+    let round = stats(&[("A", None), ("B", Some(300)), ("C", Some(50))]);
+    assert_eq!(switcher.decide(&round, "C"), switch);
+    let round = stats(&[("A", Some(300)), ("B", Some(300)), ("C", Some(50))]);
+    assert_eq!(switcher.decide(&round, "A"), Decision::Keep);
+
+    // Pattern prefers matching members regardless of margin
+    switcher.policy = AutoPolicy::Pattern;
+    switcher.pattern = Some(Regex::new("^HK").unwrap());
+    let round = stats(&[("US", Some(50)), ("HK-1", Some(200)), ("HK-2", Some(150))]);
+    let to_hk2 = Decision::Switch {
+        to: "HK-2".to_owned(),
+    };
+    assert_eq!(switcher.decide(&round, "US"), to_hk2);
+    let round = stats(&[("US", Some(50)), ("HK-1", Some(200)), ("HK-2", Some(180))]);
+    assert_eq!(switcher.decide(&round, "HK-1"), Decision::Keep);
+    let round = stats(&[("US", Some(50)), ("HK-1", None), ("HK-2", None)]);
+    assert_eq!(
+        switcher.decide(&round, "HK-1"),
+        Decision::Switch {
+            to: "US".to_owned()
+        }
+    );
+}
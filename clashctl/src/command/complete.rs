@@ -0,0 +1,250 @@
+use std::{collections::HashMap, ffi::OsString, path::PathBuf};
+
+use clap::{Arg, Command, IntoApp, Parser};
+use clashctl_core::Clash;
+
+use crate::{
+    interactive::{Flags, InteractiveError},
+    match_name, Opts, Result,
+};
+
+/// Called by completion scripts as `clashctl __complete -- <words>`, to
+/// complete names from the config and the server. Logs are not printed, as
+/// they would be taken as candidates.
+#[derive(Parser, Debug, Clone)]
+pub struct CompleteArg {
+    #[clap(
+        raw = true,
+        value_parser,
+        help = "Words of the command line, ending with the one being completed"
+    )]
+    pub words: Vec<OsString>,
+}
+
+impl CompleteArg {
+    pub fn handle(&self) {
+        let words = self
+            .words
+            .iter()
+            .map(|x| x.to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        complete(&words)
+    }
+}
+
+/// Where the word being completed is, found by walking the command line
+/// through subcommands and options of [`Opts`] like clap does
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompleteContext {
+    /// Subcommands given, e.g. `["proxy", "use"]`
+    pub commands: Vec<String>,
+    /// Positional arguments given after the subcommands
+    pub args: Vec<String>,
+    /// Id of the option the word is a value of, e.g. `server` for `-s <TAB>`
+    pub option: Option<String>,
+    /// Values of options given, by id
+    pub values: HashMap<String, String>,
+    /// The word being completed, may be empty
+    pub current: String,
+}
+
+impl CompleteContext {
+    /// `words` should start with the name of the program, and end with the word
+    /// being completed
+    pub fn new(app: &Command, words: &[String]) -> Self {
+        let mut ctx = Self::default();
+        let (current, words) = match words.split_last() {
+            Some((current, words)) => (current, words.get(1..).unwrap_or_default()),
+            None => return ctx,
+        };
+        ctx.current = current.to_owned();
+
+        let mut commands = vec![app];
+        let mut pending: Option<String> = None;
+        let mut raw = false;
+
+        for word in words {
+            // Bash splits `--opt=value` into `--opt`, `=` and `value`
+            if word == "=" && pending.is_some() {
+                continue;
+            }
+            if let Some(id) = pending.take() {
+                ctx.values.insert(id, word.to_owned());
+            } else if raw || word == "-" || !word.starts_with('-') {
+                match commands.last().and_then(|x| x.find_subcommand(word)) {
+                    Some(sub) if ctx.args.is_empty() => {
+                        ctx.commands.push(sub.get_name().to_owned());
+                        commands.push(sub);
+                    }
+                    _ => ctx.args.push(word.to_owned()),
+                }
+            } else if word == "--" {
+                raw = true;
+            } else if let Some(long) = word.strip_prefix("--") {
+                let (name, value) = match long.split_once('=') {
+                    Some((name, value)) => (name, Some(value)),
+                    None => (long, None),
+                };
+                if let Some(id) = option_id(&commands, |arg| arg.get_long() == Some(name)) {
+                    match value {
+                        Some(value) => {
+                            ctx.values.insert(id, value.to_owned());
+                        }
+                        None => pending = Some(id),
+                    }
+                }
+            } else {
+                // Short flags can be bundled, e.g. `-vv` or `-sfoo`
+                for (i, c) in word.char_indices().skip(1) {
+                    if let Some(id) = option_id(&commands, |arg| arg.get_short() == Some(c)) {
+                        match &word[i + c.len_utf8()..] {
+                            "" => pending = Some(id),
+                            value => {
+                                ctx.values.insert(id, value.to_owned());
+                            }
+                        }
+                        break;
+                    }
+                }
+            }
+        }
+        if ctx.current == "=" && pending.is_some() {
+            ctx.current.clear();
+        }
+        ctx.option = pending;
+        ctx
+    }
+
+    /// Flags to read config and connect with, from options on the command line
+    fn flags(&self) -> Flags {
+        let value = |id: &str| self.values.get(id).cloned();
+        Flags {
+            config_dir: value("config-dir").map(PathBuf::from),
+            config_path: value("config-path").map(PathBuf::from),
+            server: value("server"),
+            timeout: value("timeout").and_then(|x| x.parse().ok()),
+            ..Default::default()
+        }
+    }
+}
+
+/// Id of the option that takes a value, and is found by `f` in `commands` or
+/// their parents, as options of parents (e.g. global ones) can be given to
+/// subcommands
+fn option_id(commands: &[&Command], f: impl Fn(&Arg) -> bool) -> Option<String> {
+    commands
+        .iter()
+        .rev()
+        .find_map(|x| x.get_arguments().find(|arg| f(arg)))
+        .filter(|arg| arg.is_takes_value_set())
+        .map(|arg| arg.get_id().to_owned())
+}
+
+/// Print completions of the last word of `words`, one per line. `words`
+/// should start with `clashctl`. Nothing is printed when there's nothing to
+/// complete or on errors, so that completion scripts fall back to static
+/// completions.
+fn complete(words: &[String]) {
+    let ctx = CompleteContext::new(&Opts::into_app(), words);
+    let current = ctx.current.to_lowercase();
+    candidates(&ctx, &ctx.flags())
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|x| x.to_lowercase().starts_with(&current))
+        .for_each(|x| println!("{}", x));
+}
+
+/// What can be given as the word in `ctx`, from the config or the server
+fn candidates(ctx: &CompleteContext, flags: &Flags) -> Result<Vec<String>> {
+    let commands = ctx.commands.iter().map(String::as_str).collect::<Vec<_>>();
+    let mut ret = match (ctx.option.as_deref(), commands.as_slice(), ctx.args.len()) {
+        (Some("server"), ..)
+        | (None, ["server", "use" | "del"], _)
+        | (None, ["server", "rename"], 0) => flags
+            .get_config()?
+            .servers
+            .iter()
+            .map(|x| x.name.clone().unwrap_or_else(|| x.url.to_string()))
+            .collect(),
+        (None, ["preset", "save" | "apply"], 0) | (None, ["preset", "del"], _) => {
+            flags.get_config()?.presets.keys().cloned().collect()
+        }
+        (Some("best"), ["proxy", "select"], _)
+        | (None, ["proxy", "use"], 0)
+        | (None, ["auto"], 0) => {
+            let proxies = connect(flags)?.get_proxies()?;
+            proxies
+                .selectors()
+                .map(|(name, _)| name.to_owned())
+                .collect()
+        }
+        (None, ["proxy", "use"], 1) => {
+            let proxies = connect(flags)?.get_proxies()?;
+            let group = match_name(
+                "group",
+                &ctx.args[0],
+                proxies.selectors().map(|(name, _)| name.as_str()),
+            )?;
+            proxies
+                .get(group)
+                .and_then(|x| x.all.clone())
+                .unwrap_or_default()
+        }
+        (None, ["proxy", "test"], _) | (Some("chain"), ["conn", ..], _) => {
+            let proxies = connect(flags)?.get_proxies()?;
+            proxies.keys().cloned().collect()
+        }
+        _ => vec![],
+    };
+    ret.sort();
+    ret.dedup();
+    Ok(ret)
+}
+
+fn connect(flags: &Flags) -> Result<Clash> {
+    let server = flags
+        .get_server()?
+        .ok_or(InteractiveError::ServerNotFound)?;
+    Ok(flags.connect(&server)?)
+}
+
+#[test]
+fn test_complete_context() {
+    let app = Opts::into_app();
+    let ctx = |line: &str| {
+        let words = line.split(' ').map(ToOwned::to_owned).collect::<Vec<_>>();
+        CompleteContext::new(&app, &words)
+    };
+
+    let use_ctx = ctx("clashctl -c /tmp/c.ron -vv proxy use --server=gw Stream ");
+    assert_eq!(use_ctx.commands, ["proxy", "use"]);
+    assert_eq!(use_ctx.args, ["Stream"]);
+    assert_eq!(use_ctx.option, None);
+    assert_eq!(use_ctx.values["config-path"], "/tmp/c.ron");
+    assert_eq!(use_ctx.values["server"], "gw");
+    assert_eq!(use_ctx.current, "");
+
+    let select_ctx = ctx("clashctl proxy select --rounds 2 --best Pro");
+    assert_eq!(select_ctx.commands, ["proxy", "select"]);
+    assert!(select_ctx.args.is_empty());
+    assert_eq!(select_ctx.option.as_deref(), Some("best"));
+    assert_eq!(select_ctx.current, "Pro");
+
+    // Aliases are resolved, and subcommands are not looked for after arguments
+    let del_ctx = ctx("clashctl server del list ");
+    assert_eq!(del_ctx.commands, ["server", "del"]);
+    assert_eq!(del_ctx.args, ["list"]);
+    assert_eq!(ctx("clashctl proxy ls").commands, ["proxy"]);
+    assert_eq!(ctx("clashctl server ls ").commands, ["server", "list"]);
+    assert_eq!(ctx("clashctl -s").option, None);
+    assert_eq!(ctx("clashctl -s ").option.as_deref(), Some("server"));
+
+    // `=` of `--server=gw`, as split by bash
+    let split_ctx = ctx("clashctl --server = gw proxy use ");
+    assert_eq!(split_ctx.values["server"], "gw");
+    assert_eq!(split_ctx.commands, ["proxy", "use"]);
+    for line in ["clashctl --server = ", "clashctl --server ="] {
+        assert_eq!(ctx(line).option.as_deref(), Some("server"));
+        assert_eq!(ctx(line).current, "");
+    }
+}
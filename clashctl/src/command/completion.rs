@@ -2,11 +2,45 @@ use std::{fs::File, io::Write, path::PathBuf};
 
 use clap::{ArgEnum, IntoApp, Parser};
 use clap_complete::{generate, Shell};
-use log::warn;
+use log::{info, warn};
 
 // use crate::Result;
 use crate::{detect_shell, Opts, Result};
 
+// Completion of names of groups, proxies, servers and presets by `clashctl
+// __complete`, falling back to the static completion generated by clap when
+// it prints nothing
+
+const BASH_DYNAMIC: &str = r#"_clashctl_dynamic() {
+    local candidates
+    mapfile -t candidates < <(clashctl __complete -- "${COMP_WORDS[@]:0:COMP_CWORD+1}" 2>/dev/null)
+    if [[ ${#candidates[@]} -gt 0 ]]; then
+        COMPREPLY=("${candidates[@]// /\\ }")
+        return 0
+    fi
+    _clashctl "$@"
+}
+
+complete -F _clashctl_dynamic -o bashdefault -o default clashctl
+"#;
+
+const ZSH_DYNAMIC: &str = r#"_clashctl_dynamic() {
+    local -a candidates
+    candidates=("${(@f)$(clashctl __complete -- "${(@)words[1,CURRENT]}" 2>/dev/null)}")
+    if [[ -n "${candidates[1]}" ]]; then
+        compadd -U -- "${candidates[@]}"
+    else
+        _clashctl "$@"
+    fi
+}
+
+compdef _clashctl_dynamic clashctl
+_clashctl_dynamic "$@"
+"#;
+
+const FISH_DYNAMIC: &str = r#"complete -c clashctl -a '(clashctl __complete -- (commandline -opc) (commandline -ct) 2>/dev/null)'
+"#;
+
 #[derive(Parser, Debug)]
 #[clap(about = "Generate auto-completion scripts")]
 pub struct CompletionArg {
@@ -53,7 +87,29 @@ impl CompletionArg {
                     ),
                     None => Box::new(std::io::stdout()),
                 };
-                generate(shell, &mut Opts::into_app(), "clashctl", &mut out);
+                let mut script = vec![];
+                generate(shell, &mut Opts::into_app(), "clashctl", &mut script);
+                let script = String::from_utf8_lossy(&script);
+                let script = match shell {
+                    Shell::Bash => format!("{}\n{}", script, BASH_DYNAMIC),
+                    Shell::Fish => format!("{}\n{}", script, FISH_DYNAMIC),
+                    // The zsh script ends with calling `_clashctl`, which has to be
+                    // replaced by the dynamic one
+                    Shell::Zsh => format!(
+                        "{}\n{}",
+                        script.trim_end().trim_end_matches("_clashctl \"$@\""),
+                        ZSH_DYNAMIC
+                    ),
+                    _ => {
+                        info!(
+                            "Names of groups, proxies, servers and presets are not completed in {}",
+                            shell
+                        );
+                        script.into_owned()
+                    }
+                };
+                out.write_all(script.as_bytes())
+                    .unwrap_or_else(|e| panic!("Unable to write completion script ({})", e));
             }
         }
         Ok(())
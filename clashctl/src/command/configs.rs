@@ -0,0 +1,200 @@
+use clap::{Parser, Subcommand};
+use clashctl_core::{
+    model::{Config, Mode},
+    Clash,
+};
+use log::{info, warn};
+use owo_colors::OwoColorize;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::{interactive::Flags, ErrorKind, Render, Result};
+
+#[derive(Subcommand, Debug)]
+#[clap(about = "Interacting with runtime configs of Clash")]
+pub enum ConfigSubcommand {
+    #[clap(about = "Show current configs")]
+    Show,
+    #[clap(about = "Change configs without reloading the profile")]
+    Set(ConfigSetOpt),
+}
+
+#[derive(Parser, Debug, Clone)]
+pub struct ConfigSetOpt {
+    #[clap(
+        required = true,
+        value_name = "KEY=VALUE",
+        help = "Configs to change, e.g. `mode=global log-level=debug allow-lan=true`"
+    )]
+    pub items: Vec<String>,
+}
+
+#[derive(Parser, Debug, Clone)]
+pub struct ModeOpt {
+    #[clap(
+        possible_values = &["rule", "global", "direct"],
+        ignore_case = true,
+        help = "Mode to switch to. Show current mode if omitted"
+    )]
+    pub mode: Option<Mode>,
+}
+
+/// Configs printed by `config show` and `config set`
+#[derive(Serialize, Debug, Clone)]
+#[serde(transparent)]
+pub struct ClashConfig(pub Config);
+
+impl Render for ClashConfig {
+    fn render_table(&self) {
+        let value = serde_json::to_value(&self.0).unwrap_or_default();
+        for (key, value) in value.as_object().into_iter().flatten() {
+            let value = match value {
+                Value::String(s) => s.to_owned(),
+                Value::Array(items) => items
+                    .iter()
+                    .map(|x| x.as_str().map_or_else(|| x.to_string(), ToOwned::to_owned))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                value => value.to_string(),
+            };
+            println!("{:<16}{}", key.green(), value)
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ClashMode {
+    pub mode: Mode,
+}
+
+impl Render for ClashMode {
+    fn render_table(&self) {
+        println!("{}", self.mode.green())
+    }
+}
+
+/// Parse `key=value` pairs into a patch of configs. Values are parsed as JSON
+/// when possible, e.g. `true` or `7890`, and as strings otherwise.
+pub fn parse_patch(items: &[String]) -> Result<Map<String, Value>> {
+    items
+        .iter()
+        .map(|item| {
+            let (key, value) = item.split_once('=').ok_or_else(|| {
+                ErrorKind::InvalidConfig(item.to_owned(), "expected `KEY=VALUE`".to_owned())
+            })?;
+            let value = serde_json::from_str(value).unwrap_or_else(|_| Value::from(value));
+            Ok((key.trim().to_owned(), value))
+        })
+        .collect()
+}
+
+/// Apply `patch` to `config` locally, so that unknown keys and values of
+/// wrong types are caught before sending to Clash
+pub fn check_patch(config: &Config, patch: &Map<String, Value>) -> Result<Config> {
+    let mut merged = serde_json::to_value(config)?;
+    let mut checked = config.to_owned();
+    for (key, value) in patch {
+        let object = merged.as_object_mut().expect("Config should be an object");
+        if !object.contains_key(key) {
+            let keys = object.keys().map(String::as_str).collect::<Vec<_>>();
+            return Err(ErrorKind::InvalidConfig(
+                key.to_owned(),
+                format!("unknown key, expected one of {}", keys.join(", ")),
+            )
+            .into());
+        }
+        object.insert(key.to_owned(), value.to_owned());
+        // Check after each item to tell which one is invalid
+        checked = serde_json::from_value(merged.clone())
+            .map_err(|e| ErrorKind::InvalidConfig(format!("{}={}", key, value), e.to_string()))?;
+    }
+    Ok(checked)
+}
+
+/// Connect to the server, which must not be read-only if `write`
+fn connect(flags: &Flags, write: bool) -> Result<Option<Clash>> {
+    let server = match flags.get_server()? {
+        Some(server) => server,
+        None => {
+            warn!("No server configured yet. Use `clashctl server add` first.");
+            return Ok(None);
+        }
+    };
+    info!("Using {}", server);
+    if write {
+        server.check_writable()?;
+    }
+    Ok(Some(flags.connect(&server)?))
+}
+
+impl ConfigSubcommand {
+    pub fn handle(&self, flags: &Flags) -> Result<()> {
+        let clash = match connect(flags, matches!(self, Self::Set(_)))? {
+            Some(clash) => clash,
+            None => return Ok(()),
+        };
+
+        match self {
+            Self::Show => flags.output.print(&ClashConfig(clash.get_configs()?))?,
+            Self::Set(opt) => {
+                let patch = parse_patch(&opt.items)?;
+                check_patch(&clash.get_configs()?, &patch)?;
+                clash.patch_configs(&patch)?;
+                info!("Configs updated");
+                flags.output.print(&ClashConfig(clash.get_configs()?))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ModeOpt {
+    pub fn handle(&self, flags: &Flags) -> Result<()> {
+        let clash = match connect(flags, self.mode.is_some())? {
+            Some(clash) => clash,
+            None => return Ok(()),
+        };
+
+        if let Some(mode) = self.mode {
+            clash.patch_configs(&serde_json::json!({ "mode": mode }))?;
+            info!("Switched to {} mode", mode);
+        }
+        flags.output.print(&ClashMode {
+            mode: clash.get_configs()?.mode,
+        })
+    }
+}
+
+#[test]
+fn test_config_patch() {
+    use clashctl_core::model::Level;
+
+    let config = Config {
+        port: 7890,
+        socks_port: 7891,
+        redir_port: 0,
+        tproxy_port: 0,
+        mixed_port: 0,
+        allow_lan: false,
+        ipv6: false,
+        mode: Mode::Rule,
+        log_level: Level::Info,
+        bind_address: "*".to_owned(),
+        authentication: vec![],
+    };
+    let patch = |items: &[&str]| {
+        parse_patch(&items.iter().map(|x| x.to_string()).collect::<Vec<_>>())
+            .and_then(|patch| check_patch(&config, &patch))
+    };
+
+    let patched = patch(&["mode=global", "log-level=debug", "allow-lan=true"]).unwrap();
+    assert_eq!(patched.mode, Mode::Global);
+    assert_eq!(patched.log_level, Level::Debug);
+    assert!(patched.allow_lan);
+    assert_eq!(patch(&["port=1080"]).unwrap().port, 1080);
+
+    assert!(patch(&["mode"]).is_err());
+    assert!(patch(&["mod=global"]).is_err());
+    assert!(patch(&["mode=fast"]).is_err());
+    assert!(patch(&["port=abc"]).is_err());
+}
@@ -0,0 +1,399 @@
+use std::{collections::HashMap, io::IsTerminal, thread::sleep, time::Duration};
+
+use bytesize::ByteSize;
+use chrono::{DateTime, Local, Utc};
+use clap::{Parser, Subcommand};
+use clashctl_core::{
+    model::{Connection, ConnectionWithSpeed},
+    strum::VariantNames,
+    Clash,
+};
+use log::{info, warn};
+use owo_colors::OwoColorize;
+use requestty::{prompt_one, Question};
+use serde::Serialize;
+use terminal_size::{terminal_size, Height, Width};
+
+use crate::{
+    interactive::{ConSort, ConSortBy, Flags, SortOrder, Sortable},
+    ErrorKind, Render, Result, HMS,
+};
+
+#[derive(Subcommand, Debug)]
+#[clap(about = "Interacting with connections")]
+pub enum ConnSubcommand {
+    #[clap(alias = "ls", about = "List connections (alias ls)")]
+    List(ConnListOpt),
+    #[clap(about = "Close connections")]
+    Close(ConnCloseOpt),
+    #[clap(about = "Print connections as they open and close")]
+    Watch(ConnWatchOpt),
+}
+
+#[derive(Parser, Debug, Clone, Default)]
+pub struct ConnFilterOpt {
+    #[clap(
+        long,
+        help = "Only connections whose host or destination IP contains this"
+    )]
+    pub host: Option<String>,
+
+    #[clap(
+        long,
+        help = "Only connections whose rule type or payload contains this"
+    )]
+    pub rule: Option<String>,
+
+    #[clap(long, help = "Only connections going through this proxy or group")]
+    pub chain: Option<String>,
+
+    #[clap(long, help = "Only connections from this source IP")]
+    pub source: Option<String>,
+
+    #[clap(
+        long,
+        parse(try_from_str = humantime::parse_duration),
+        help = "Only connections older than this, e.g. `30s` or `5m`"
+    )]
+    pub older_than: Option<Duration>,
+
+    #[clap(
+        long,
+        parse(try_from_str = humantime::parse_duration),
+        help = "Only connections newer than this, e.g. `30s` or `5m`"
+    )]
+    pub newer_than: Option<Duration>,
+}
+
+impl ConnFilterOpt {
+    /// Whether `connection` passes all filters. Text is compared
+    /// case-insensitively.
+    pub fn matches(&self, connection: &Connection) -> bool {
+        let contains =
+            |haystack: &str, needle: &str| haystack.to_lowercase().contains(&needle.to_lowercase());
+        let meta = &connection.metadata;
+        let age = (Utc::now() - connection.start).to_std().unwrap_or_default();
+
+        self.host
+            .as_ref()
+            .is_none_or(|x| contains(&meta.host, x) || contains(&meta.destination_ip, x))
+            && self.rule.as_ref().is_none_or(|x| {
+                contains(connection.rule.as_ref(), x) || contains(&connection.rule_payload, x)
+            })
+            && self
+                .chain
+                .as_ref()
+                .is_none_or(|x| connection.chains.iter().any(|c| c.eq_ignore_ascii_case(x)))
+            && self.source.as_ref().is_none_or(|x| &meta.source_ip == x)
+            && self.older_than.is_none_or(|x| age > x)
+            && self.newer_than.is_none_or(|x| age < x)
+    }
+}
+
+#[derive(Parser, Debug, Clone)]
+pub struct ConnListOpt {
+    #[clap(flatten)]
+    pub filter: ConnFilterOpt,
+
+    #[clap(
+        long,
+        default_value = "time",
+        possible_values = ConSortBy::VARIANTS,
+        ignore_case = true,
+    )]
+    pub sort_by: ConSortBy,
+
+    #[clap(
+        long,
+        default_value = "descendant",
+        possible_values = &["ascendant", "descendant"],
+    )]
+    pub sort_order: SortOrder,
+}
+
+#[derive(Parser, Debug, Clone)]
+pub struct ConnCloseOpt {
+    #[clap(flatten)]
+    pub filter: ConnFilterOpt,
+
+    #[clap(long, help = "Only print connections to be closed")]
+    pub dry_run: bool,
+
+    #[clap(short, long, help = "Close without confirmation")]
+    pub yes: bool,
+}
+
+#[derive(Parser, Debug, Clone)]
+pub struct ConnWatchOpt {
+    #[clap(flatten)]
+    pub filter: ConnFilterOpt,
+
+    #[clap(
+        long,
+        help = "Interval between two polls, in ms. Default to `refresh.connections` of the \
+                server, or 1000"
+    )]
+    pub interval: Option<u64>,
+}
+
+/// Connections listed by `conn list` and `conn close`
+#[derive(Serialize, Debug, Clone)]
+#[serde(transparent)]
+pub struct ConnList(pub Vec<ConnectionWithSpeed>);
+
+impl Render for ConnList {
+    fn render_table(&self) {
+        if self.0.is_empty() {
+            return;
+        }
+        let (Width(terminal_width), _) = terminal_size().unwrap_or((Width(70), Height(0)));
+        println!("\n{:-<1$}", "", terminal_width as usize);
+        println!(
+            "{:<40}{:<12}{:<12}{:<12}{:<30}CHAIN",
+            "HOST", "DOWN", "UP", "AGE", "RULE"
+        );
+        println!("{:-<1$}", "", terminal_width as usize);
+        for con in &self.0 {
+            let connection = &con.connection;
+            println!(
+                "{:<40}{:<12}{:<12}{:<12}{:<30}{}",
+                describe_host(connection),
+                ByteSize(connection.download).to_string_as(true),
+                ByteSize(connection.upload).to_string_as(true),
+                (Utc::now() - connection.start).hms(),
+                describe_rule(connection),
+                connection.chains.join(" - ")
+            )
+        }
+        println!("{:-<1$}", "", terminal_width as usize);
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ConnEventKind {
+    Open,
+    Close,
+}
+
+/// A connection opened or closed, printed by `conn watch`
+#[derive(Serialize, Debug, Clone)]
+pub struct ConnEvent {
+    pub time: DateTime<Local>,
+    pub event: ConnEventKind,
+    pub connection: Connection,
+}
+
+impl Render for ConnEvent {
+    fn render_table(&self) {
+        let connection = &self.connection;
+        let time = self.time.format("%T").to_string();
+        match self.event {
+            ConnEventKind::Open => println!(
+                "{} {} {} ({} → {})",
+                time.dimmed(),
+                "+".green(),
+                describe_host(connection),
+                describe_rule(connection),
+                connection.chains.join(" - ")
+            ),
+            ConnEventKind::Close => println!(
+                "{} {} {} (▼ {} ▲ {}, {})",
+                time.dimmed(),
+                "-".red(),
+                describe_host(connection),
+                ByteSize(connection.download).to_string_as(true),
+                ByteSize(connection.upload).to_string_as(true),
+                (Utc::now() - connection.start).hms()
+            ),
+        }
+    }
+}
+
+fn describe_host(connection: &Connection) -> String {
+    let meta = &connection.metadata;
+    format!(
+        "{}:{}",
+        if meta.host.is_empty() {
+            &meta.destination_ip
+        } else {
+            &meta.host
+        },
+        meta.destination_port
+    )
+}
+
+fn describe_rule(connection: &Connection) -> String {
+    if connection.rule_payload.is_empty() {
+        connection.rule.to_string()
+    } else {
+        format!("{}({})", connection.rule, connection.rule_payload)
+    }
+}
+
+impl ConnSubcommand {
+    pub fn handle(&self, flags: &Flags) -> Result<()> {
+        let server = match flags.get_server()? {
+            Some(server) => server,
+            None => {
+                warn!("No server configured yet. Use `clashctl server add` first.");
+                return Ok(());
+            }
+        };
+        info!("Using {}", server);
+        let clash = flags.connect(&server)?;
+
+        match self {
+            Self::List(opt) => {
+                let mut list = filtered(&clash, &opt.filter)?;
+                list.sort_with(&ConSort::new(opt.sort_by, opt.sort_order));
+                flags.output.print(&ConnList(list))?;
+            }
+            Self::Close(opt) => {
+                let list = filtered(&clash, &opt.filter)?;
+                if list.is_empty() {
+                    warn!("No connections to close");
+                    return Ok(());
+                }
+                if opt.dry_run {
+                    info!("{} connection(s) would be closed", list.len());
+                    return flags.output.print(&ConnList(list));
+                }
+                server.check_writable()?;
+                if !opt.yes && !std::io::stdin().is_terminal() {
+                    return Err(ErrorKind::ConfirmationRequired.into());
+                }
+                // Show what is going to be closed before asking
+                let shown = !opt.yes && flags.output.is_table();
+                if shown {
+                    ConnList(list.clone()).render_table();
+                }
+                if !opt.yes {
+                    let confirmed = prompt_one(
+                        Question::confirm("confirm")
+                            .message(format!("Close {} connection(s)?", list.len()))
+                            .default(false)
+                            .build(),
+                    )?;
+                    if !confirmed.as_bool().unwrap_or_default() {
+                        info!("Aborted");
+                        return Ok(());
+                    }
+                }
+                // Only the listed ones, not those opened since
+                for con in &list {
+                    clash.close_one_connection(&con.connection.id)?;
+                }
+                info!("Closed {} connection(s)", list.len());
+                if !shown {
+                    flags.output.print(&ConnList(list))?;
+                }
+            }
+            Self::Watch(opt) => {
+                let interval = opt
+                    .interval
+                    .unwrap_or_else(|| server.refresh.unwrap_or_default().connections);
+                let mut known: HashMap<String, Connection> = HashMap::new();
+                let mut first = true;
+                loop {
+                    match clash.get_connections() {
+                        Ok(connections) => {
+                            let current = connections
+                                .connections
+                                .into_iter()
+                                .filter(|x| opt.filter.matches(x))
+                                .map(|x| (x.id.to_owned(), x))
+                                .collect::<HashMap<_, _>>();
+                            // Connections alive before watching are printed as opened as well
+                            let mut opened = current
+                                .values()
+                                .filter(|x| !known.contains_key(&x.id))
+                                .collect::<Vec<_>>();
+                            opened.sort_by_key(|x| x.start);
+                            for connection in opened {
+                                print_event(flags, ConnEventKind::Open, connection)?;
+                            }
+                            for connection in known.values() {
+                                if !current.contains_key(&connection.id) {
+                                    print_event(flags, ConnEventKind::Close, connection)?;
+                                }
+                            }
+                            if first {
+                                info!("Watching connections, press Ctrl-C to stop");
+                                first = false;
+                            }
+                            known = current;
+                        }
+                        Err(e) => warn!("Failed to get connections: {}", e),
+                    }
+                    sleep(Duration::from_millis(interval))
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn filtered(clash: &Clash, filter: &ConnFilterOpt) -> Result<Vec<ConnectionWithSpeed>> {
+    Ok(clash
+        .get_connections()?
+        .connections
+        .into_iter()
+        .filter(|x| filter.matches(x))
+        .map(Into::into)
+        .collect())
+}
+
+fn print_event(flags: &Flags, event: ConnEventKind, connection: &Connection) -> Result<()> {
+    flags.output.print(&ConnEvent {
+        time: Local::now(),
+        event,
+        connection: connection.to_owned(),
+    })
+}
+
+#[test]
+fn test_conn_filter() {
+    use clashctl_core::model::{Metadata, RuleType};
+
+    let connection = Connection {
+        id: "1".into(),
+        upload: 0,
+        download: 0,
+        metadata: Metadata {
+            connection_type: "HTTPS".into(),
+            source_ip: "192.168.1.2".into(),
+            source_port: "51234".into(),
+            destination_ip: "1.2.3.4".into(),
+            destination_port: "443".into(),
+            host: "www.Example.com".into(),
+            network: "tcp".into(),
+        },
+        rule: RuleType::DomainSuffix,
+        rule_payload: "example.com".into(),
+        start: Utc::now() - chrono::Duration::seconds(90),
+        chains: vec!["HK-1".into(), "Proxy".into()],
+    };
+    let filter = |filter: ConnFilterOpt| filter.matches(&connection);
+
+    assert!(filter(ConnFilterOpt::default()));
+    assert!(filter(ConnFilterOpt {
+        host: Some("example".into()),
+        chain: Some("proxy".into()),
+        older_than: Some(Duration::from_secs(60)),
+        ..Default::default()
+    }));
+    assert!(filter(ConnFilterOpt {
+        rule: Some("suffix".into()),
+        source: Some("192.168.1.2".into()),
+        ..Default::default()
+    }));
+    assert!(!filter(ConnFilterOpt {
+        newer_than: Some(Duration::from_secs(60)),
+        ..Default::default()
+    }));
+    assert!(!filter(ConnFilterOpt {
+        chain: Some("HK".into()),
+        ..Default::default()
+    }));
+}
@@ -0,0 +1,407 @@
+use std::{
+    env,
+    fs::OpenOptions,
+    io::{stdout, IsTerminal},
+    path::Path,
+    time::Duration,
+};
+
+use owo_colors::OwoColorize;
+use serde::Serialize;
+
+use crate::{
+    interactive::{sibling, Config, ConfigData, Flags, SYSTEM_CONFIG_ENV},
+    ErrorKind, Render, Result, ServerCheck,
+};
+
+/// Smallest terminal the TUI is usable in
+const MIN_TERMINAL_SIZE: (u16, u16) = (80, 24);
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Ok,
+    Warn,
+    Fail,
+}
+
+/// Result of one check of `doctor`, with a fix if it's not ok
+#[derive(Serialize, Debug, Clone)]
+pub struct Diagnosis {
+    pub check: &'static str,
+    pub status: Status,
+    pub detail: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fix: Option<String>,
+}
+
+impl Diagnosis {
+    fn ok(check: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            check,
+            status: Status::Ok,
+            detail: detail.into(),
+            fix: None,
+        }
+    }
+
+    fn warn(check: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            check,
+            status: Status::Warn,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+
+    fn fail(check: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            check,
+            status: Status::Fail,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(transparent)]
+pub struct Diagnoses(pub Vec<Diagnosis>);
+
+impl Render for Diagnoses {
+    fn render_table(&self) {
+        for diagnosis in &self.0 {
+            let mark = match diagnosis.status {
+                Status::Ok => "✔".green().to_string(),
+                Status::Warn => "!".yellow().to_string(),
+                Status::Fail => "✘".red().to_string(),
+            };
+            println!("{} {:<14}{}", mark, diagnosis.check, diagnosis.detail);
+            if let Some(ref fix) = diagnosis.fix {
+                println!("  {:<14}{} {}", "", "→".dimmed(), fix);
+            }
+        }
+    }
+}
+
+fn check_config(flags: &Flags, result: &mut Vec<Diagnosis>) -> Option<ConfigData> {
+    const CHECK: &str = "Config file";
+
+    if let Some(path) = flags.system_config_path() {
+        match Config::read_system(&path) {
+            Err(_) if !path.exists() => result.push(Diagnosis::fail(
+                "System config",
+                format!("{} does not exist", path.display()),
+                format!("Unset `{}`, or point it to a file", SYSTEM_CONFIG_ENV),
+            )),
+            Ok(_) => result.push(Diagnosis::ok("System config", path.display().to_string())),
+            Err(e) => {
+                result.push(Diagnosis::fail(
+                    "System config",
+                    format!("{} ({})", path.display(), e),
+                    "Ask your admin to fix it",
+                ));
+                return None;
+            }
+        }
+    }
+
+    let path = match flags.config_path() {
+        Ok(path) => path,
+        Err(e) => {
+            result.push(Diagnosis::fail(
+                CHECK,
+                e.to_string(),
+                "Use `--config-dir` or `--config-path`",
+            ));
+            return None;
+        }
+    };
+    if !path.exists() {
+        result.push(Diagnosis::warn(
+            CHECK,
+            format!("{} does not exist", path.display()),
+            "Run `clashctl server add` to create it",
+        ));
+        return None;
+    }
+
+    // Checked before reading, as writing the config fixes permissions
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        if let Ok(meta) = path.metadata() {
+            if meta.permissions().mode() & 0o077 != 0 {
+                result.push(Diagnosis::warn(
+                    "Permissions",
+                    format!("{} is accessible by other users", path.display()),
+                    format!("Run `chmod 600 {}`", path.display()),
+                ));
+            }
+        }
+    }
+
+    let config = match flags.get_config() {
+        Ok(config) => config.get_inner().to_owned(),
+        Err(e) => {
+            let backup = sibling(&path, "bak");
+            let fix = if backup.exists() {
+                format!(
+                    "Fix {}, or restore the last good one with `cp {} {}`",
+                    path.display(),
+                    backup.display(),
+                    path.display()
+                )
+            } else {
+                format!(
+                    "Fix {}, or move it away and run `clashctl server add`",
+                    path.display()
+                )
+            };
+            result.push(Diagnosis::fail(CHECK, e.to_string(), fix));
+            return None;
+        }
+    };
+    result.push(Diagnosis::ok(CHECK, path.display().to_string()));
+
+    if config.servers.is_empty() {
+        result.push(Diagnosis::warn(
+            "Servers",
+            "No server configured",
+            "Run `clashctl server add`",
+        ));
+    } else if !config
+        .using
+        .as_ref()
+        .is_some_and(|using| config.servers.iter().any(|x| &x.url == using))
+    {
+        result.push(Diagnosis::warn(
+            "Servers",
+            format!("{} configured, but none is active", config.servers.len()),
+            "Run `clashctl server use`",
+        ));
+    } else {
+        result.push(Diagnosis::ok(
+            "Servers",
+            format!("{} configured", config.servers.len()),
+        ));
+    }
+
+    for server in &config.servers {
+        if let Err(e) = server.resolve_secret() {
+            result.push(Diagnosis::fail(
+                "Secret",
+                e.to_string(),
+                "Fix the secret source, or add the server again with another one",
+            ));
+        }
+    }
+
+    for test in &config.group_tests {
+        if let Some(e) = test.pattern.error() {
+            result.push(Diagnosis::fail(
+                "Group tests",
+                format!(
+                    "`{}` is not a valid regex ({})",
+                    test.pattern,
+                    e.to_string().lines().last().unwrap_or_default()
+                ),
+                "Fix the pattern in `group_tests`",
+            ));
+        }
+    }
+
+    Some(config)
+}
+
+fn check_log_file(path: Option<&Path>) -> Diagnosis {
+    const CHECK: &str = "Log file";
+
+    let path = match path {
+        Some(path) => path,
+        None => return Diagnosis::ok(CHECK, "Not set, TUI logs are not saved"),
+    };
+    if path.exists() {
+        return match OpenOptions::new().append(true).open(path) {
+            Ok(_) => Diagnosis::ok(CHECK, path.display().to_string()),
+            Err(e) => Diagnosis::fail(
+                CHECK,
+                format!("Cannot write {} ({})", path.display(), e),
+                format!("Check permissions of {}", path.display()),
+            ),
+        };
+    }
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() && !dir.is_dir() => Diagnosis::fail(
+            CHECK,
+            format!("Directory of {} does not exist", path.display()),
+            format!("Run `mkdir -p {}`", dir.display()),
+        ),
+        _ => Diagnosis::ok(
+            CHECK,
+            format!("{} (created on start of TUI)", path.display()),
+        ),
+    }
+}
+
+fn check_terminal(result: &mut Vec<Diagnosis>) {
+    const CHECK: &str = "Terminal";
+
+    if !stdout().is_terminal() {
+        result.push(Diagnosis::warn(
+            CHECK,
+            "Output is not a terminal",
+            "Run clashctl in a terminal to use the TUI",
+        ));
+        return;
+    }
+
+    let (min_width, min_height) = MIN_TERMINAL_SIZE;
+    match crossterm::terminal::size() {
+        Ok((width, height)) if width < min_width || height < min_height => {
+            result.push(Diagnosis::warn(
+                CHECK,
+                format!("Size is {}x{}", width, height),
+                format!(
+                    "Enlarge the terminal to at least {}x{}",
+                    min_width, min_height
+                ),
+            ))
+        }
+        Ok((width, height)) => result.push(Diagnosis::ok(
+            CHECK,
+            format!("Size is {}x{}", width, height),
+        )),
+        Err(e) => result.push(Diagnosis::warn(
+            CHECK,
+            format!("Cannot get size ({})", e),
+            "Use a terminal emulator with ANSI support",
+        )),
+    }
+
+    match env::var("TERM") {
+        Ok(term) if term != "dumb" => {
+            let colors = match env::var("COLORTERM") {
+                Ok(x) if x == "truecolor" || x == "24bit" => "true color",
+                _ if term.contains("256color") => "256 colors",
+                _ => "basic colors",
+            };
+            result.push(Diagnosis::ok(
+                "Colors",
+                format!("TERM={}, {}", term, colors),
+            ))
+        }
+        _ => result.push(Diagnosis::warn(
+            "Colors",
+            "TERM is not set or `dumb`",
+            "Set TERM, e.g. `export TERM=xterm-256color`",
+        )),
+    }
+}
+
+fn diagnose_server(check: &ServerCheck) -> Diagnosis {
+    const CHECK: &str = "Server";
+
+    let url = check.url.as_str();
+    if !check.reachable {
+        return Diagnosis::fail(
+            CHECK,
+            format!(
+                "{} is not reachable ({})",
+                url,
+                check.error.as_deref().unwrap_or("unknown error")
+            ),
+            format!(
+                "Make sure Clash is running and `external-controller` in its config matches {}",
+                url
+            ),
+        );
+    }
+    if check.auth == Some(false) {
+        return Diagnosis::fail(
+            CHECK,
+            format!("Secret is not accepted by {}", url),
+            "Make the secret match `secret` in the config of Clash",
+        );
+    }
+    if check.auth.is_none() {
+        return Diagnosis::fail(
+            CHECK,
+            format!(
+                "{} responds with errors ({})",
+                url,
+                check.error.as_deref().unwrap_or("unknown error")
+            ),
+            "Make sure the URL points to the API of Clash, not a proxy port",
+        );
+    }
+    let version = format!(
+        "{} {}, {}ms",
+        check.flavor.as_deref().unwrap_or("Clash"),
+        check.version.as_deref().unwrap_or("unknown"),
+        check.latency.unwrap_or_default()
+    );
+    if check.traffic != Some(true) {
+        return Diagnosis::warn(
+            CHECK,
+            format!("{}, but traffic stream did not open", version),
+            "Make sure proxies in front of Clash, e.g. nginx, do not buffer streamed responses",
+        );
+    }
+    Diagnosis::ok(CHECK, format!("{} ({})", url, version))
+}
+
+pub fn doctor(flags: &Flags) -> Result<()> {
+    let mut result = vec![];
+
+    let config = check_config(flags, &mut result);
+    result.push(check_log_file(
+        config.as_ref().and_then(|x| x.tui.log_file.as_deref()),
+    ));
+    check_terminal(&mut result);
+
+    match flags.get_server() {
+        Ok(Some(server)) => result.push(diagnose_server(&ServerCheck::run(
+            &server,
+            Duration::from_millis(flags.timeout(Some(&server))),
+        ))),
+        Ok(None) => {}
+        // Already reported by the check of config file
+        Err(_) if config.is_none() => {}
+        Err(e) => result.push(Diagnosis::fail(
+            "Server",
+            e.to_string(),
+            "Run `clashctl server list` to see configured servers",
+        )),
+    }
+
+    let failed = result.iter().filter(|x| x.status == Status::Fail).count();
+    flags.output.print(&Diagnoses(result))?;
+    match failed {
+        0 => Ok(()),
+        failed => Err(ErrorKind::CheckFailed(failed).into()),
+    }
+}
+
+#[test]
+fn test_diagnose_server() {
+    let check = |reachable, auth, traffic| ServerCheck {
+        name: None,
+        url: url::Url::parse("http://127.0.0.1:9090").unwrap(),
+        reachable,
+        auth,
+        version: auth.and(Some("1.18.0".to_owned())),
+        flavor: auth.and(Some("Premium".to_owned())),
+        latency: auth.and(Some(1)),
+        traffic,
+        error: None,
+    };
+    let status = |x: ServerCheck| diagnose_server(&x).status;
+
+    assert_eq!(status(check(true, Some(true), Some(true))), Status::Ok);
+    assert_eq!(status(check(true, Some(true), Some(false))), Status::Warn);
+    assert_eq!(status(check(true, Some(false), None)), Status::Fail);
+    assert_eq!(status(check(true, None, None)), Status::Fail);
+    assert_eq!(status(check(false, None, None)), Status::Fail);
+}
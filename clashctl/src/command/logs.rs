@@ -0,0 +1,186 @@
+use std::{io::Write, path::PathBuf, time::Instant};
+
+use bytesize::ByteSize;
+use chrono::{DateTime, Local};
+use clap::Parser;
+use clashctl_core::model::Level;
+use log::{info, warn};
+use owo_colors::OwoColorize;
+use regex::Regex;
+use serde::Serialize;
+
+use crate::{
+    interactive::Flags, spawn_stream, ErrorKind, Render, Result, RotatingFile, StreamEvent,
+};
+
+#[derive(Parser, Debug, Clone)]
+pub struct LogOpt {
+    #[clap(
+        short,
+        long,
+        default_value = "info",
+        possible_values = &["error", "warn", "info", "debug"],
+        ignore_case = true,
+        help = "Lowest level of logs to show"
+    )]
+    pub level: Level,
+
+    #[clap(short, long, help = "Only show logs matching this regex")]
+    pub grep: Option<Regex>,
+
+    #[clap(
+        long,
+        help = "Number logs and show the time since start, instead of wall clock"
+    )]
+    pub since_start: bool,
+
+    #[clap(long, help = "Also append logs to this file")]
+    pub file: Option<PathBuf>,
+
+    #[clap(
+        long,
+        default_value = "10MiB",
+        help = "Size of log file before it is rotated"
+    )]
+    pub max_size: ByteSize,
+
+    #[clap(
+        long,
+        default_value = "3",
+        help = "Number of rotated log files to keep"
+    )]
+    pub keep: usize,
+}
+
+/// A log line printed by `log`
+#[derive(Serialize, Debug, Clone)]
+pub struct LogRecord {
+    pub time: DateTime<Local>,
+    /// Number of the log since start, with `--since-start`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub count: Option<usize>,
+    /// Seconds since start, with `--since-start`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub elapsed: Option<f64>,
+    #[serde(rename = "type")]
+    pub level: Level,
+    pub payload: String,
+}
+
+impl Render for LogRecord {
+    fn render_table(&self) {
+        // Same as the formatter of `utils::init_logger`
+        let level = match self.level {
+            Level::Debug => "Debug".blue().to_string(),
+            Level::Info => " Info".green().to_string(),
+            Level::Warning => " Warn".yellow().to_string(),
+            Level::Error => "Error".red().to_string(),
+        };
+        let prefix = match (self.count, self.elapsed) {
+            (Some(count), Some(elapsed)) => format!("#{:<5} +{:>9.3}s", count, elapsed),
+            _ => self.time.format("%T").to_string(),
+        };
+        println!("{} {} > {}", prefix.dimmed(), level, self.payload)
+    }
+}
+
+/// Number of logs of each level since start
+#[derive(Debug, Default)]
+struct Counter {
+    error: usize,
+    warning: usize,
+    info: usize,
+    debug: usize,
+}
+
+impl Counter {
+    fn add(&mut self, level: &Level) {
+        match level {
+            Level::Error => self.error += 1,
+            Level::Warning => self.warning += 1,
+            Level::Info => self.info += 1,
+            Level::Debug => self.debug += 1,
+        }
+    }
+
+    fn total(&self) -> usize {
+        self.error + self.warning + self.info + self.debug
+    }
+}
+
+impl LogOpt {
+    pub fn handle(&self, flags: &Flags) -> Result<()> {
+        let server = match flags.get_server()? {
+            Some(server) => server,
+            None => {
+                warn!("No server configured yet. Use `clashctl server add` first.");
+                return Ok(());
+            }
+        };
+        info!("Using {}", server);
+        let clash = flags.connect(&server)?;
+
+        let mut file = match self.file {
+            Some(ref path) => Some(
+                RotatingFile::open(path, self.max_size.as_u64(), self.keep)
+                    .map_err(ErrorKind::LogFileError)?,
+            ),
+            None => None,
+        };
+
+        let events = spawn_stream(clash.get_log_with_level(&self.level)?)?;
+
+        let start = Instant::now();
+        let mut counter = Counter::default();
+        let result = loop {
+            let log = match events.recv() {
+                Ok(StreamEvent::Item(log)) => log,
+                Ok(StreamEvent::Closed(Some(e))) => break Err(e.into()),
+                Ok(StreamEvent::Closed(None)) => {
+                    warn!("Log stream closed by server");
+                    break Ok(());
+                }
+                Ok(StreamEvent::Interrupted) | Err(_) => break Ok(()),
+            };
+            if log.log_type > self.level
+                || !self.grep.as_ref().is_none_or(|x| x.is_match(&log.payload))
+            {
+                continue;
+            }
+            counter.add(&log.log_type);
+
+            let record = LogRecord {
+                time: Local::now(),
+                count: self.since_start.then_some(counter.total()),
+                elapsed: self.since_start.then(|| start.elapsed().as_secs_f64()),
+                level: log.log_type,
+                payload: log.payload.trim_end().to_owned(),
+            };
+            if let Some(ref mut file) = file {
+                let line = format!(
+                    "{} {} {}\n",
+                    record.time.to_rfc3339(),
+                    record.level,
+                    record.payload
+                );
+                file.write_all(line.as_bytes())
+                    .map_err(ErrorKind::LogFileError)?;
+            }
+            flags.output.print(&record)?;
+        };
+
+        if let Some(ref mut file) = file {
+            file.flush().map_err(ErrorKind::LogFileError)?;
+        }
+        info!(
+            "{} log(s) in {:.1}s: {} error, {} warn, {} info, {} debug",
+            counter.total(),
+            start.elapsed().as_secs_f64(),
+            counter.error,
+            counter.warning,
+            counter.info,
+            counter.debug
+        );
+        result
+    }
+}
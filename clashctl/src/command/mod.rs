@@ -3,7 +3,10 @@ use log::LevelFilter;
 
 use crate::{interactive::Flags, ui::TuiOpt, utils::init_logger};
 
-mod_use::mod_use!(completion, proxy, server);
+mod_use::mod_use!(
+    auto, complete, completion, configs, conn, doctor, logs, preset, proxy, rule, server, sources,
+    traffic
+);
 
 #[derive(Parser, Debug)]
 #[clap(
@@ -28,6 +31,26 @@ pub enum Cmd {
     Proxy(ProxySubcommand),
     #[clap(subcommand)]
     Server(ServerSubcommand),
+    #[clap(subcommand)]
+    Preset(PresetSubcommand),
+    #[clap(subcommand)]
+    Rule(RuleSubcommand),
+    #[clap(subcommand)]
+    Conn(ConnSubcommand),
+    #[clap(subcommand)]
+    Config(ConfigSubcommand),
+    #[clap(about = "Show or switch proxy mode of Clash")]
+    Mode(ModeOpt),
+    #[clap(about = "Stream logs of Clash")]
+    Log(LogOpt),
+    #[clap(about = "Monitor traffic, and summarize it when stopped")]
+    Traffic(TrafficOpt),
+    #[clap(about = "Keep switching a selector group to its best member")]
+    Auto(AutoOpt),
+    #[clap(about = "Check config, terminal and server, and suggest fixes")]
+    Doctor,
+    #[clap(about = "Show config of clashctl, and which file each value comes from")]
+    Sources,
     #[clap(alias = "comp")]
     Completion(CompletionArg),
 }
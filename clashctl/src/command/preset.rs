@@ -0,0 +1,218 @@
+use std::collections::BTreeMap;
+
+use clap::{Parser, Subcommand};
+use clashctl_core::Clash;
+use log::{info, warn};
+use owo_colors::OwoColorize;
+use serde::Serialize;
+
+use crate::{
+    interactive::{
+        apply_preset, plan_preset, snapshot_preset, Flags, Preset, PresetAction, PresetEntry,
+        Server,
+    },
+    match_name, Render, Result,
+};
+
+#[derive(Subcommand, Debug)]
+#[clap(about = "Save and apply selections of all selector groups")]
+pub enum PresetSubcommand {
+    #[clap(about = "Save current selection of every selector group")]
+    Save(PresetSaveOpt),
+    #[clap(about = "Select members saved in a preset")]
+    Apply(PresetApplyOpt),
+    #[clap(alias = "ls", about = "List saved presets")]
+    List,
+    #[clap(about = "Remove presets")]
+    Del(PresetDelOpt),
+}
+
+#[derive(Parser, Debug, Clone)]
+pub struct PresetSaveOpt {
+    #[clap(help = "Name of the preset. Replace the existing one with the same name")]
+    pub name: String,
+}
+
+#[derive(Parser, Debug, Clone)]
+pub struct PresetApplyOpt {
+    #[clap(help = "Name of the preset, case-insensitive and fuzzy")]
+    pub name: String,
+
+    #[clap(long, help = "Only show what would be changed")]
+    pub dry_run: bool,
+}
+
+#[derive(Parser, Debug, Clone)]
+pub struct PresetDelOpt {
+    #[clap(required = true, help = "Names of presets to remove")]
+    pub names: Vec<String>,
+}
+
+fn connect(flags: &Flags) -> Result<Option<(Server, Clash)>> {
+    let server = match flags.get_server()? {
+        Some(server) => server,
+        None => {
+            warn!("No server configured yet. Use `clashctl server add` first.");
+            return Ok(None);
+        }
+    };
+    info!("Using {}", server);
+    let clash = flags.connect(&server)?;
+    Ok(Some((server, clash)))
+}
+
+impl PresetSubcommand {
+    pub fn handle(&self, flags: &Flags) -> Result<()> {
+        let mut config = flags.get_config()?;
+
+        match self {
+            Self::Save(opt) => {
+                let (_, clash) = match connect(flags)? {
+                    Some(x) => x,
+                    None => return Ok(()),
+                };
+                let preset = snapshot_preset(&clash.get_proxies()?);
+                info!(
+                    "Saving selections of {} groups as `{}`",
+                    preset.len(),
+                    opt.name
+                );
+                config.update(|config| {
+                    config.presets.insert(opt.name.to_owned(), preset);
+                    Ok(())
+                })?;
+            }
+            Self::Apply(opt) => {
+                let name = match_name(
+                    "preset",
+                    &opt.name,
+                    config.presets.keys().map(String::as_str),
+                )?;
+                let preset = &config.presets[name];
+                let (server, clash) = match connect(flags)? {
+                    Some(x) => x,
+                    None => return Ok(()),
+                };
+                let entries = if opt.dry_run {
+                    info!("Dry run, nothing is changed");
+                    plan_preset(&clash.get_proxies()?, preset)
+                } else {
+                    server.check_writable()?;
+                    info!("Applying `{}`", name);
+                    apply_preset(&clash, preset)?
+                };
+                let missing = entries
+                    .iter()
+                    .filter(|x| {
+                        matches!(
+                            x.action,
+                            PresetAction::MissingGroup | PresetAction::MissingMember
+                        )
+                    })
+                    .count();
+                if missing > 0 {
+                    warn!(
+                        "{} group(s) or member(s) no longer exist, run `clashctl preset save {}` \
+                         to update the preset",
+                        missing, name
+                    );
+                }
+                return flags.output.print(&PresetReport(entries));
+            }
+            Self::List => {
+                if config.presets.is_empty() {
+                    warn!("No preset saved yet. Use `clashctl preset save <name>` first.");
+                }
+            }
+            Self::Del(opt) => {
+                // Find all of them first, so that nothing is removed on typos
+                let names = opt
+                    .names
+                    .iter()
+                    .map(|name| {
+                        match_name("preset", name, config.presets.keys().map(String::as_str))
+                            .map(ToOwned::to_owned)
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                config.update(|config| {
+                    for name in names {
+                        if config.remove_preset(&name)? {
+                            info!("Removed `{}`, the one of system config is back", name);
+                        } else {
+                            info!("Removed `{}`", name);
+                        }
+                    }
+                    Ok(())
+                })?;
+            }
+        }
+        flags.output.print(&PresetList(config.presets.clone()))
+    }
+}
+
+/// Saved presets, printed after `preset save`, `list` and `del`
+#[derive(Serialize, Debug, Clone)]
+#[serde(transparent)]
+pub struct PresetList(pub BTreeMap<String, Preset>);
+
+impl Render for PresetList {
+    fn render_table(&self) {
+        for (name, preset) in &self.0 {
+            println!("{} ({} groups)", name.green(), preset.len());
+            let width = preset
+                .keys()
+                .map(|x| x.chars().count())
+                .max()
+                .unwrap_or_default();
+            for (group, proxy) in preset {
+                println!("  {:<width$}  → {}", group, proxy, width = width);
+            }
+        }
+    }
+}
+
+/// Result of `preset apply`
+#[derive(Serialize, Debug, Clone)]
+#[serde(transparent)]
+pub struct PresetReport(pub Vec<PresetEntry>);
+
+impl Render for PresetReport {
+    fn render_table(&self) {
+        let width = |f: fn(&PresetEntry) -> &str, header: &str| {
+            self.0
+                .iter()
+                .map(|x| f(x).chars().count())
+                .max()
+                .unwrap_or_default()
+                .max(header.len())
+                + 2
+        };
+        let (group_width, proxy_width) =
+            (width(|x| &x.group, "GROUP"), width(|x| &x.proxy, "PROXY"));
+        println!(
+            "{:<group_width$}{:<proxy_width$}ACTION",
+            "GROUP",
+            "PROXY",
+            group_width = group_width,
+            proxy_width = proxy_width
+        );
+        for entry in &self.0 {
+            let action = format!("{:?}", entry.action);
+            let action = match entry.action {
+                PresetAction::Applied => action.green().to_string(),
+                PresetAction::Unchanged => action.dimmed().to_string(),
+                PresetAction::MissingGroup | PresetAction::MissingMember => {
+                    action.yellow().to_string()
+                }
+            };
+            println!(
+                "{:<group_width$}{:<proxy_width$}{}",
+                entry.group,
+                entry.proxy,
+                action,
+                group_width = group_width,
+                proxy_width = proxy_width
+            )
+        }
+    }
+}
@@ -1,14 +1,20 @@
 use std::time::Duration;
 
 use clap::{Parser, Subcommand};
-use clashctl_core::{model::ProxyType, strum::VariantNames};
-use log::{error, info, warn};
+use clashctl_core::{
+    model::{Proxies, ProxyType},
+    strum::VariantNames,
+};
+use log::{debug, error, info, warn};
 use owo_colors::OwoColorize;
+use regex::Regex;
 use requestty::{prompt_one, Answer, ListItem, Question};
+use serde::Serialize;
+use url::Url;
 
 use crate::{
-    interactive::{Flags, ProxySortBy, SortOrder},
-    RenderList, Result,
+    interactive::{Flags, GroupTestConfig, ProxySort, ProxySortBy, Server, SortOrder, Sortable},
+    match_name, pick_best, DelayReport, DelayStats, DelayTest, Error, ProxyList, Render, Result,
 };
 // use crate::{Result};
 
@@ -42,7 +48,178 @@ pub enum ProxySubcommand {
     #[clap(alias = "ls", about = "List proxies (alias ls)")]
     List(ProxyListOpt),
     #[clap(about = "Set active proxy")]
-    Use,
+    Use(ProxyUseOpt),
+    #[clap(about = "Test latency of proxies")]
+    Test(ProxyTestOpt),
+    #[clap(about = "Test members of a selector group and select the best one")]
+    Select(ProxySelectOpt),
+}
+
+#[derive(Parser, Debug, Clone)]
+pub struct ProxyUseOpt {
+    #[clap(help = "Selector group to change, case-insensitive and fuzzy. Prompt if omitted")]
+    pub group: Option<String>,
+
+    #[clap(help = "Proxy to use in the group, case-insensitive and fuzzy. Prompt if omitted")]
+    pub proxy: Option<String>,
+}
+
+#[derive(Parser, Debug, Clone)]
+pub struct DelayTestOpt {
+    #[clap(long, default_value = "3", help = "Number of tests of each proxy")]
+    pub rounds: usize,
+
+    #[clap(
+        long,
+        default_value = "8",
+        help = "Number of tests running at the same time"
+    )]
+    pub concurrency: usize,
+
+    #[clap(
+        long,
+        help = "Url to test against. Default to `--test-url`, or the group test of the group"
+    )]
+    pub url: Option<Url>,
+
+    #[clap(
+        long,
+        help = "Timeout of each test, in ms. Default to `--timeout`, or the group test of the \
+                group"
+    )]
+    pub timeout: Option<u64>,
+}
+
+impl DelayTestOpt {
+    /// Test of members of a group, with `group` being its group test
+    pub fn delay_test(
+        &self,
+        flags: &Flags,
+        server: &Server,
+        group: Option<&GroupTestConfig>,
+    ) -> DelayTest {
+        DelayTest {
+            url: self
+                .url
+                .clone()
+                .unwrap_or_else(|| flags.test_url(Some(server), group))
+                .to_string(),
+            timeout: self
+                .timeout
+                .unwrap_or_else(|| flags.test_timeout(Some(server), group)),
+            rounds: self.rounds,
+            concurrency: self.concurrency,
+        }
+    }
+
+    /// Longest timeout of tests with any of `group_tests`
+    pub fn max_timeout(
+        &self,
+        flags: &Flags,
+        server: &Server,
+        group_tests: &[GroupTestConfig],
+    ) -> u64 {
+        self.timeout
+            .unwrap_or_else(|| flags.max_test_timeout(Some(server), group_tests))
+    }
+}
+
+#[derive(Parser, Debug, Clone)]
+pub struct ProxyTestOpt {
+    #[clap(
+        help = "Groups or proxies to test, case-insensitive and fuzzy. Groups are expanded to \
+                their members. Test all proxies if omitted"
+    )]
+    pub targets: Vec<String>,
+
+    #[clap(flatten)]
+    pub test: DelayTestOpt,
+
+    #[clap(
+        long,
+        default_value = "delay",
+        possible_values = &["type", "name", "delay"],
+    )]
+    pub sort_by: ProxySortBy,
+
+    #[clap(
+        long,
+        default_value = "ascendant",
+        possible_values = &["ascendant", "descendant"],
+    )]
+    pub sort_order: SortOrder,
+}
+
+impl ProxyTestOpt {
+    /// Resolve targets into proxies to be tested, in the order given. They
+    /// are batched by the group they are members of, or `None` for proxies
+    /// given by name, as groups may have their own group tests.
+    pub fn resolve(&self, proxies: &Proxies) -> Result<Vec<TestBatch>> {
+        if self.targets.is_empty() {
+            let mut list = proxies
+                .normal()
+                .map(|(name, proxy)| (name.to_owned(), proxy.proxy_type))
+                .collect::<Vec<_>>();
+            list.sort();
+            return Ok(vec![(None, list)]);
+        }
+
+        let mut batches: Vec<TestBatch> = vec![];
+        for target in &self.targets {
+            let name = match_name("proxy or group", target, proxies.keys().map(String::as_str))?;
+            let proxy = &proxies[name];
+            let (group, members) = match proxy.all {
+                Some(ref all) => (Some(name), all.iter().map(String::as_str).collect()),
+                None => (None, vec![name]),
+            };
+            let batch_index = match batches.iter().position(|(x, _)| x.as_deref() == group) {
+                Some(index) => index,
+                None => {
+                    batches.push((group.map(ToOwned::to_owned), vec![]));
+                    batches.len() - 1
+                }
+            };
+            for member in members {
+                let tested = batches
+                    .iter()
+                    .flat_map(|(_, list)| list)
+                    .any(|(name, _)| name == member);
+                if !tested {
+                    let proxy_type = proxies
+                        .get(member)
+                        .map_or(ProxyType::Unknown, |x| x.proxy_type);
+                    batches[batch_index].1.push((member.to_owned(), proxy_type))
+                }
+            }
+        }
+        batches.retain(|(_, list)| !list.is_empty());
+        Ok(batches)
+    }
+}
+
+/// Proxies tested together, with the group they are members of
+pub type TestBatch = (Option<String>, Vec<(String, ProxyType)>);
+
+#[derive(Parser, Debug, Clone)]
+pub struct ProxySelectOpt {
+    #[clap(
+        long,
+        value_name = "GROUP",
+        help = "Selector group to optimize, case-insensitive and fuzzy"
+    )]
+    pub best: String,
+
+    #[clap(long, help = "Only consider proxies whose name matches this regex")]
+    pub filter: Option<Regex>,
+
+    #[clap(
+        long,
+        help = "Only consider proxies with average delay of at most this, in ms"
+    )]
+    pub max_delay: Option<u64>,
+
+    #[clap(flatten)]
+    pub test: DelayTestOpt,
 }
 
 #[derive(Parser, Debug, Clone)]
@@ -86,65 +263,144 @@ pub struct ProxyListOpt {
     pub plain: bool,
 }
 
+#[derive(Serialize, Debug, Clone)]
+pub struct ProxySelection {
+    pub group: String,
+    pub proxy: String,
+}
+
+impl Render for ProxySelection {
+    fn render_table(&self) {
+        println!("{} → {}", self.group.green(), self.proxy.green())
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct BestSelection {
+    pub group: String,
+    pub before: String,
+    pub after: String,
+    /// Test result of `before`, `None` if it is not a candidate
+    pub before_stats: Option<DelayStats>,
+    pub after_stats: DelayStats,
+}
+
+impl Render for BestSelection {
+    fn render_table(&self) {
+        let describe = |name: &str, stats: Option<&DelayStats>| match stats {
+            Some(DelayStats {
+                avg: Some(avg),
+                jitter: Some(jitter),
+                ..
+            }) => format!("{} ({} ms, jitter {} ms)", name, avg, jitter),
+            Some(DelayStats { avg: Some(avg), .. }) => format!("{} ({} ms)", name, avg),
+            _ => format!("{} (untested or unavailable)", name),
+        };
+        let before = describe(&self.before, self.before_stats.as_ref());
+        let after = describe(&self.after, Some(&self.after_stats));
+        if self.before == self.after {
+            println!("{}: {} is already the best", self.group.green(), after)
+        } else {
+            println!(
+                "{}: {} → {}",
+                self.group.green(),
+                before.red(),
+                after.green()
+            )
+        }
+    }
+}
+
 impl ProxySubcommand {
     pub fn handle(&self, flags: &Flags) -> Result<()> {
-        let config = flags.get_config()?;
-        let server = match config.using_server() {
-            Some(server) => server.to_owned(),
+        let server = match flags.get_server()? {
+            Some(server) => server,
             None => {
                 warn!("No server configured yet. Use `clashctl server add` first.");
                 return Ok(());
             }
         };
         info!("Using {}", server);
-        let clash = server.into_clash_with_timeout(Some(Duration::from_millis(flags.timeout)))?;
+        // Only latency tests read config, so that other commands work without it
+        let config = match self {
+            ProxySubcommand::Test(_) | ProxySubcommand::Select(_) => flags.get_config_or_default(),
+            _ => Default::default(),
+        };
+
+        // Delay tests are run by Clash, so requests have to wait for them
+        let timeout = match self {
+            ProxySubcommand::Test(ProxyTestOpt { test, .. })
+            | ProxySubcommand::Select(ProxySelectOpt { test, .. }) => {
+                flags.timeout(Some(&server)) + test.max_timeout(flags, &server, &config.group_tests)
+            }
+            _ => flags.timeout(Some(&server)),
+        };
+        let clash = server
+            .clone()
+            .into_clash_with_timeout(Some(Duration::from_millis(timeout)))?;
 
         match self {
             ProxySubcommand::List(opt) => {
                 let proxies = clash.get_proxies()?;
-                proxies.render_list(opt);
+                flags.output.print(&ProxyList::new(&proxies, opt))?;
             }
-            ProxySubcommand::Use => {
+            ProxySubcommand::Use(opt) => {
                 let proxies = clash.get_proxies()?;
-                let mut groups = proxies
-                    .iter()
-                    .filter(|(_, p)| p.proxy_type.is_selector())
-                    .map(|(name, _)| name)
-                    .filter(|name| !["GLOBAL", "REJECT"].contains(&name.as_str()))
-                    .collect::<Vec<_>>();
-                groups.sort();
-                let group_selected = match prompt_one(
-                    Question::select("proxy")
-                        .message("Which group to change?")
-                        .choices(groups)
-                        .build(),
-                ) {
-                    Ok(result) => result.as_list_item().unwrap().text.to_owned(),
-                    Err(e) => {
-                        error!("Error selecting proxy: {}", e);
-                        return Err(e.into());
+                let group_selected = match opt.group {
+                    Some(ref group) => match_name(
+                        "group",
+                        group,
+                        proxies.selectors().map(|(name, _)| name.as_str()),
+                    )?
+                    .to_owned(),
+                    None => {
+                        let mut groups = proxies
+                            .selectors()
+                            .map(|(name, _)| name)
+                            .filter(|name| !["GLOBAL", "REJECT"].contains(&name.as_str()))
+                            .collect::<Vec<_>>();
+                        groups.sort();
+                        match prompt_one(
+                            Question::select("proxy")
+                                .message("Which group to change?")
+                                .choices(groups)
+                                .build(),
+                        ) {
+                            Ok(result) => result.as_list_item().unwrap().text.to_owned(),
+                            Err(e) => {
+                                error!("Error selecting proxy: {}", e);
+                                return Err(e.into());
+                            }
+                        }
                     }
                 };
                 let proxy = clash.get_proxy(&group_selected)?;
 
                 // all / now only occurs when proxy_type is [`ProxyType::Selector`]
                 let members = proxy.all.unwrap();
-                let now = proxy.now.unwrap();
-                let cur_index = members.iter().position(|x| x == &now).unwrap();
-                let mut question = Question::select("proxy")
-                    .message("Which proxy to use?")
-                    .choices(members);
-                if cur_index != 0 {
-                    question = question.default(cur_index)
-                }
-                let member_selected = match prompt_one(question.build()) {
-                    Ok(result) => match result {
-                        Answer::ListItem(ListItem { text, .. }) => text,
-                        _ => unreachable!(),
-                    },
-                    Err(e) => {
-                        error!("Error selecting proxy: {}", e);
-                        return Err(e.into());
+                let member_selected = match opt.proxy {
+                    Some(ref member) => {
+                        match_name("proxy", member, members.iter().map(String::as_str))?.to_owned()
+                    }
+                    None => {
+                        let now = proxy.now.unwrap();
+                        let cur_index = members.iter().position(|x| x == &now).unwrap();
+                        let mut question = Question::select("proxy")
+                            .message("Which proxy to use?")
+                            .choices(members);
+                        if cur_index != 0 {
+                            question = question.default(cur_index)
+                        }
+                        match prompt_one(question.build()) {
+                            Ok(result) => match result {
+                                Answer::ListItem(ListItem { text, .. }) => text,
+                                _ => unreachable!(),
+                            },
+                            Err(e) => {
+                                error!("Error selecting proxy: {}", e);
+                                return Err(e.into());
+                            }
+                        }
                     }
                 };
                 info!(
@@ -152,14 +408,108 @@ impl ProxySubcommand {
                     group_selected.green(),
                     member_selected.green()
                 );
+                server.check_writable()?;
                 clash.set_proxygroup_selected(&group_selected, &member_selected)?;
-                info!("Done!")
+                flags.output.print(&ProxySelection {
+                    group: group_selected,
+                    proxy: member_selected,
+                })?;
+            }
+            ProxySubcommand::Test(opt) => {
+                let proxies = clash.get_proxies()?;
+                let batches = opt.resolve(&proxies)?;
+                if batches.is_empty() {
+                    warn!("No proxies to test");
+                    return Ok(());
+                }
+                info!(
+                    "Testing {} proxies for {} round(s)",
+                    batches.iter().map(|(_, list)| list.len()).sum::<usize>(),
+                    opt.test.rounds
+                );
+                let mut stats = vec![];
+                for (group, targets) in batches {
+                    let group_test = group.as_deref().and_then(|x| config.group_test(x));
+                    let test = opt.test.delay_test(flags, &server, group_test);
+                    debug!(
+                        "Testing {} against {}",
+                        group.as_deref().unwrap_or("proxies"),
+                        test.url
+                    );
+                    stats.extend(test.run(&clash, &targets)?);
+                }
+                stats.sort_with(&ProxySort::new(opt.sort_by, opt.sort_order));
+                flags.output.print(&DelayReport(stats))?;
+            }
+            ProxySubcommand::Select(opt) => {
+                let proxies = clash.get_proxies()?;
+                let group = match_name(
+                    "group",
+                    &opt.best,
+                    proxies.selectors().map(|(name, _)| name.as_str()),
+                )?;
+                let proxy = &proxies[group];
+                // all / now only occurs when proxy_type is [`ProxyType::Selector`]
+                let before = proxy.now.to_owned().unwrap();
+                let targets = proxy
+                    .all
+                    .iter()
+                    .flatten()
+                    .filter_map(|name| proxies.get(name).map(|x| (name, x.proxy_type)))
+                    .filter(|(name, proxy_type)| {
+                        proxy_type.is_normal()
+                            && opt.filter.as_ref().is_none_or(|x| x.is_match(name))
+                    })
+                    .map(|(name, proxy_type)| (name.to_owned(), proxy_type))
+                    .collect::<Vec<_>>();
+
+                info!(
+                    "Testing {} members of {} for {} round(s)",
+                    targets.len(),
+                    group,
+                    opt.test.rounds
+                );
+                let stats = opt
+                    .test
+                    .delay_test(flags, &server, config.group_test(group))
+                    .run(&clash, &targets)?;
+                let best = pick_best(&stats, opt.max_delay).ok_or_else(|| {
+                    Error::not_found("available proxy", describe_filter(group, opt))
+                })?;
+
+                if best.name != before {
+                    info!(
+                        "Setting group {} to use {}",
+                        group.green(),
+                        best.name.green()
+                    );
+                    server.check_writable()?;
+                    clash.set_proxygroup_selected(group, &best.name)?;
+                }
+                flags.output.print(&BestSelection {
+                    group: group.to_owned(),
+                    after: best.name.to_owned(),
+                    before_stats: stats.iter().find(|x| x.name == before).cloned(),
+                    before,
+                    after_stats: best.to_owned(),
+                })?;
             }
         }
         Ok(())
     }
 }
 
+fn describe_filter(group: &str, opt: &ProxySelectOpt) -> String {
+    let mut desc = group.to_owned();
+    if let Some(ref filter) = opt.filter {
+        desc += &format!(" with name ~ /{}/", filter);
+    }
+    if let Some(max_delay) = opt.max_delay {
+        desc += &format!(" within {} ms", max_delay);
+    }
+    desc
+}
+
 #[test]
 fn test_proxy_type() {
     let string = "direct";
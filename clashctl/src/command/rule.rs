@@ -0,0 +1,409 @@
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand};
+use clashctl_core::{
+    model::{Connection, Rule, RuleType, Rules},
+    strum::VariantNames,
+    RulePosition,
+};
+use log::{info, warn};
+use owo_colors::OwoColorize;
+use requestty::{prompt_one, Question};
+use serde::Serialize;
+use terminal_size::{terminal_size, Height, Width};
+
+use crate::{
+    interactive::{
+        insert_rule_into_profile, Flags, InteractiveError, RuleSort, RuleSortBy, SortMethod,
+        SortOrder,
+    },
+    Error, Render, Result,
+};
+
+#[derive(Subcommand, Debug)]
+#[clap(about = "Interacting with rules")]
+pub enum RuleSubcommand {
+    #[clap(alias = "ls", about = "List rules (alias ls)")]
+    List(RuleListOpt),
+    #[clap(about = "Count rules by target proxy and by type")]
+    Stats,
+    #[clap(about = "Generate a rule from a live connection and insert it into a local profile")]
+    Gen(RuleGenOpt),
+}
+
+#[derive(Parser, Debug, Clone)]
+pub struct RuleListOpt {
+    #[clap(
+        long = "type",
+        possible_values = RuleType::VARIANTS,
+        ignore_case = true,
+        help = "Only rules of these types"
+    )]
+    pub rule_type: Vec<RuleType>,
+
+    #[clap(
+        short,
+        long,
+        help = "Only rules targeting this proxy, case-insensitive"
+    )]
+    pub proxy: Option<String>,
+
+    #[clap(
+        long,
+        help = "Only rules whose payload contains this, case-insensitive"
+    )]
+    pub payload: Option<String>,
+
+    #[clap(
+        long,
+        possible_values = RuleSortBy::VARIANTS,
+        ignore_case = true,
+        help = "Sort rules. Default to the order Clash matches them"
+    )]
+    pub sort_by: Option<RuleSortBy>,
+
+    #[clap(
+        long,
+        default_value = "ascendant",
+        possible_values = &["ascendant", "descendant"],
+    )]
+    pub sort_order: SortOrder,
+}
+
+impl RuleListOpt {
+    pub fn matches(&self, rule: &Rule) -> bool {
+        (self.rule_type.is_empty() || self.rule_type.contains(&rule.rule_type))
+            && self
+                .proxy
+                .as_ref()
+                .is_none_or(|x| rule.proxy.eq_ignore_ascii_case(x))
+            && self
+                .payload
+                .as_ref()
+                .is_none_or(|x| rule.payload.to_lowercase().contains(&x.to_lowercase()))
+    }
+}
+
+#[derive(Parser, Debug, Clone)]
+pub struct RuleGenOpt {
+    #[clap(
+        help = "Connection ID, host or destination IP to generate rule from. Select from live \
+                connections if omitted"
+    )]
+    pub target: Option<String>,
+
+    #[clap(
+        long = "type",
+        default_value = "DomainSuffix",
+        possible_values = &["Domain", "DomainSuffix", "DomainKeyword", "IPCIDR"],
+        ignore_case = true,
+        help = "Type of the generated rule"
+    )]
+    pub rule_type: RuleType,
+
+    #[clap(
+        short,
+        long,
+        default_value = "REJECT",
+        help = "Policy of the generated rule, e.g. REJECT, DIRECT or a proxy group"
+    )]
+    pub policy: String,
+
+    #[clap(
+        long,
+        help = "Prefix length of IPCIDR rules. Default to a single address"
+    )]
+    pub prefix: Option<u8>,
+
+    #[clap(
+        long,
+        help = "Profile to insert the rule into. Default to `profile.path` in config"
+    )]
+    pub profile: Option<PathBuf>,
+
+    #[clap(
+        long,
+        default_value = "top",
+        help = "Where to insert the rule: `top`, `bottom` (before MATCH) or an index"
+    )]
+    pub position: RulePosition,
+
+    #[clap(long, help = "Reload the profile after inserting the rule")]
+    pub reload: bool,
+
+    #[clap(long, help = "Only print the generated rule")]
+    pub dry_run: bool,
+}
+
+impl RuleSubcommand {
+    pub fn handle(&self, flags: &Flags) -> Result<()> {
+        let server = match flags.get_server()? {
+            Some(server) => server,
+            None => {
+                warn!("No server configured yet. Use `clashctl server add` first.");
+                return Ok(());
+            }
+        };
+        info!("Using {}", server);
+        let clash = flags.connect(&server)?;
+
+        match self {
+            Self::List(opt) => {
+                let mut list = clash
+                    .get_rules()?
+                    .rules
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, rule)| RuleEntry { index, rule })
+                    .filter(|x| opt.matches(&x.rule))
+                    .collect::<Vec<_>>();
+                if let Some(by) = opt.sort_by {
+                    let sort = RuleSort::new(by, opt.sort_order);
+                    list.sort_by(|a, b| sort.sort_fn(&a.rule, &b.rule));
+                }
+                flags.output.print(&RuleList(list))?;
+            }
+            Self::Stats => {
+                let rules = clash.get_rules()?;
+                flags.output.print(&RuleStats::new(&rules))?;
+            }
+            Self::Gen(opt) => {
+                let connections = clash.get_connections()?.connections;
+                if connections.is_empty() {
+                    warn!("No live connections");
+                    return Ok(());
+                }
+
+                let connection = match opt.target {
+                    Some(ref target) => find_connection(&connections, target)?,
+                    None => select_connection(&connections)?,
+                };
+
+                let rule = connection
+                    .metadata
+                    .to_rule(opt.rule_type, &opt.policy, opt.prefix)
+                    .and_then(|rule| rule.to_profile_line())
+                    .ok_or_else(|| {
+                        Error::not_found(
+                            "host or IP",
+                            format!("{} rule of connection {}", opt.rule_type, connection.id),
+                        )
+                    })?;
+
+                if opt.dry_run {
+                    return flags.output.print(&GeneratedRule {
+                        rule,
+                        profile: None,
+                        index: None,
+                    });
+                }
+
+                let config = flags.get_config()?;
+                let path = opt
+                    .profile
+                    .as_ref()
+                    .or(config.profile.path.as_ref())
+                    .ok_or(InteractiveError::ProfileNotSet)?;
+
+                let reload = opt.reload || config.profile.reload;
+                if reload {
+                    server.check_writable()?;
+                }
+
+                info!("Generated {}", rule.green());
+                let index =
+                    insert_rule_into_profile(path, &rule, opt.position, reload.then_some(&clash))?;
+                flags.output.print(&GeneratedRule {
+                    rule,
+                    profile: Some(path.to_owned()),
+                    index: Some(index),
+                })?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct RuleEntry {
+    /// Position of the rule in Clash
+    pub index: usize,
+    #[serde(flatten)]
+    pub rule: Rule,
+}
+
+/// Rules listed by `rule list`
+#[derive(Serialize, Debug, Clone)]
+#[serde(transparent)]
+pub struct RuleList(pub Vec<RuleEntry>);
+
+impl Render for RuleList {
+    fn render_table(&self) {
+        let (Width(terminal_width), _) = terminal_size().unwrap_or((Width(70), Height(0)));
+        println!("\n{:-<1$}", "", terminal_width as usize);
+        println!("{:<6}{:<16}{:<40}PROXY", "#", "TYPE", "PAYLOAD");
+        println!("{:-<1$}", "", terminal_width as usize);
+        for RuleEntry { index, rule } in &self.0 {
+            println!(
+                "{:<6}{}{:<40}{}",
+                index,
+                format!("{:<16}", rule.rule_type).green(),
+                if rule.payload.is_empty() {
+                    "-"
+                } else {
+                    &rule.payload
+                },
+                rule.proxy
+            )
+        }
+        println!("{:-<1$}", "", terminal_width as usize);
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct RuleCount {
+    pub name: String,
+    pub count: usize,
+}
+
+/// Breakdown of rules printed by `rule stats`
+#[derive(Serialize, Debug, Clone)]
+pub struct RuleStats {
+    pub total: usize,
+    pub by_proxy: Vec<RuleCount>,
+    pub by_type: Vec<RuleCount>,
+}
+
+impl RuleStats {
+    pub fn new(rules: &Rules) -> Self {
+        let sorted = |counts: Vec<(String, usize)>| {
+            let mut counts = counts
+                .into_iter()
+                .map(|(name, count)| RuleCount { name, count })
+                .collect::<Vec<_>>();
+            counts.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.name.cmp(&b.name)));
+            counts
+        };
+        Self {
+            total: rules.rules.len(),
+            by_proxy: sorted(
+                rules
+                    .target_frequency()
+                    .into_iter()
+                    .map(|(k, v)| (k.to_owned(), v))
+                    .collect(),
+            ),
+            by_type: sorted(
+                rules
+                    .type_frequency()
+                    .into_iter()
+                    .map(|(k, v)| (k.to_string(), v))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl Render for RuleStats {
+    fn render_table(&self) {
+        let print_counts = |title: &str, counts: &[RuleCount]| {
+            println!("\n{:<24}{:<8}SHARE", title, "COUNT");
+            for RuleCount { name, count } in counts {
+                println!(
+                    "{:<24}{:<8}{:.1}%",
+                    name,
+                    count,
+                    *count as f64 * 100. / self.total.max(1) as f64
+                )
+            }
+        };
+        println!("{} rules", self.total);
+        print_counts("PROXY", &self.by_proxy);
+        print_counts("TYPE", &self.by_type);
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct GeneratedRule {
+    pub rule: String,
+    /// Profile the rule is inserted into, `None` for dry runs
+    pub profile: Option<PathBuf>,
+    /// Index of the rule in the profile, `None` for dry runs
+    pub index: Option<usize>,
+}
+
+impl Render for GeneratedRule {
+    fn render_table(&self) {
+        println!("{}", self.rule)
+    }
+}
+
+fn describe(connection: &Connection) -> String {
+    let meta = &connection.metadata;
+    format!(
+        "{}:{} ({} → {})",
+        if meta.host.is_empty() {
+            &meta.destination_ip
+        } else {
+            &meta.host
+        },
+        meta.destination_port,
+        connection.rule,
+        connection.chains.join(" - ")
+    )
+}
+
+/// Find the connection with id `target`, or the connection whose host or
+/// destination IP is (or contains) `target`. Multiple connections to the same
+/// host are treated as one.
+fn find_connection<'a>(connections: &'a [Connection], target: &str) -> Result<&'a Connection> {
+    if let Some(connection) = connections.iter().find(|x| x.id == target) {
+        return Ok(connection);
+    }
+
+    let exact = connections
+        .iter()
+        .filter(|x| x.metadata.host == target || x.metadata.destination_ip == target)
+        .collect::<Vec<_>>();
+
+    let matched = if exact.is_empty() {
+        connections
+            .iter()
+            .filter(|x| {
+                x.metadata.host.contains(target) || x.metadata.destination_ip.contains(target)
+            })
+            .collect::<Vec<_>>()
+    } else {
+        exact
+    };
+
+    let mut hosts = matched
+        .iter()
+        .map(|x| {
+            if x.metadata.host.is_empty() {
+                x.metadata.destination_ip.as_str()
+            } else {
+                x.metadata.host.as_str()
+            }
+        })
+        .collect::<Vec<_>>();
+    hosts.sort_unstable();
+    hosts.dedup();
+
+    match hosts.len() {
+        0 => Err(Error::not_found("connection", target)),
+        1 => Ok(matched[0]),
+        _ => Err(Error::ambiguous("host", target, hosts)),
+    }
+}
+
+fn select_connection(connections: &[Connection]) -> Result<&Connection> {
+    let ans = prompt_one(
+        Question::select("connection")
+            .message("Which connection to generate rule from?")
+            .choices(connections.iter().map(describe))
+            .build(),
+    )?;
+    let index = ans.as_list_item().unwrap().index;
+    Ok(&connections[index])
+}
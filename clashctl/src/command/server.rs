@@ -1,13 +1,25 @@
-use clap::Subcommand;
+use std::{
+    env, fs,
+    io::{self, Read},
+    path::PathBuf,
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use clap::{ArgGroup, Parser, Subcommand};
+use clashctl_core::{Clash, Profile};
 use log::{debug, info, warn};
 use owo_colors::OwoColorize;
+use rayon::prelude::*;
 use requestty::{prompt, prompt_one, Answers, Question};
+use serde::{Deserialize, Serialize};
 use terminal_size::{terminal_size, Height, Width};
 use url::Url;
 
 use crate::{
-    interactive::{Flags, Server},
-    Result,
+    interactive::{Config, Flags, ImportAction, ImportEntry, InteractiveResult, Server, URL_ENV},
+    ErrorKind, Render, Result,
 };
 
 // use crate::Result;
@@ -16,100 +28,356 @@ use crate::{
 #[clap(about = "Interacting with servers")]
 pub enum ServerSubcommand {
     #[clap(alias = "a", about = "Add new server (alias a)")]
-    Add,
+    Add(ServerAddOpt),
     #[clap(about = "Select active server")]
-    Use,
+    Use(ServerUseOpt),
     #[clap(alias = "ls", about = "Show current active server")]
     List,
     #[clap(about = "Remove servers")]
-    Del,
+    Del(ServerDelOpt),
+    #[clap(about = "Give a server a new name")]
+    Rename(ServerRenameOpt),
+    #[clap(about = "Check connection, secret and version of servers")]
+    Check,
+    #[clap(about = "Print servers, to be imported by `server import`")]
+    Export(ServerExportOpt),
+    #[clap(about = "Import servers exported by `server export`")]
+    Import(ServerImportOpt),
+}
+
+#[derive(Parser, Debug, Clone)]
+pub struct ServerExportOpt {
+    #[clap(long, help = "Include plain text secrets")]
+    pub with_secrets: bool,
+}
+
+#[derive(Parser, Debug, Clone)]
+pub struct ServerImportOpt {
+    #[clap(help = "File of servers in YAML or JSON, or `-` for stdin")]
+    pub file: PathBuf,
+
+    #[clap(long, help = "Replace existing servers that differ from imported ones")]
+    pub overwrite: bool,
+
+    #[clap(long, help = "Only show what would be imported")]
+    pub dry_run: bool,
+}
+
+/// Config files of Clash and mihomo tried by `server add --detect`. Relative
+/// ones are under the home directory
+const DETECT_PATHS: &[&str] = &[
+    ".config/mihomo/config.yaml",
+    ".config/clash.meta/config.yaml",
+    ".config/clash/config.yaml",
+    "/etc/mihomo/config.yaml",
+    "/etc/clash/config.yaml",
+];
+
+#[derive(Parser, Debug, Clone)]
+#[clap(group = ArgGroup::new("secrets").requires("url"))]
+pub struct ServerAddOpt {
+    #[clap(long, help = "URL of Clash API. Prompt for the server if omitted")]
+    pub url: Option<Url>,
+
+    #[clap(
+        long,
+        group = "secrets",
+        help = "Secret of Clash API, stored in plain text"
+    )]
+    pub secret: Option<String>,
+
+    #[clap(
+        long,
+        group = "secrets",
+        value_name = "VAR",
+        help = "Read secret from this environment variable"
+    )]
+    pub secret_env: Option<String>,
+
+    #[clap(
+        long,
+        group = "secrets",
+        value_name = "PATH",
+        help = "Read secret from this file"
+    )]
+    pub secret_file: Option<PathBuf>,
+
+    #[clap(
+        long,
+        group = "secrets",
+        value_name = "CMD",
+        help = "Read secret from output of this command, e.g. `pass show clash/gw`"
+    )]
+    pub secret_cmd: Option<String>,
+
+    #[clap(long, help = "Name to refer to the server by")]
+    pub name: Option<String>,
+
+    #[clap(
+        long,
+        conflicts_with_all = &["url", "secrets"],
+        help = "Find URL and secret in config of a local Clash or mihomo"
+    )]
+    pub detect: bool,
+
+    #[clap(
+        long,
+        value_name = "PATH",
+        requires = "detect",
+        help = "Config of Clash to detect from, instead of common paths"
+    )]
+    pub from: Option<PathBuf>,
+}
+
+#[derive(Parser, Debug, Clone)]
+pub struct ServerUseOpt {
+    #[clap(help = "Name or URL of the server. Prompt for it if omitted")]
+    pub server: Option<String>,
+}
+
+#[derive(Parser, Debug, Clone)]
+pub struct ServerDelOpt {
+    #[clap(help = "Names or URLs of servers to remove. Prompt for them if omitted")]
+    pub servers: Vec<String>,
+}
+
+#[derive(Parser, Debug, Clone)]
+pub struct ServerRenameOpt {
+    #[clap(help = "Current name or URL of the server")]
+    pub server: String,
+
+    #[clap(help = "New name of the server")]
+    pub name: String,
+}
+
+/// Text of a server in prompts
+fn describe(server: &Server) -> String {
+    match server.name {
+        Some(ref name) => format!("{} ({})", name, server.url),
+        None => server.url.to_string(),
+    }
+}
+
+impl ServerAddOpt {
+    fn prompt(&self) -> Result<Server> {
+        let mut questions = vec![
+            Question::input("url")
+                .message("URL of Clash API")
+                .validate(|input, _| match Url::parse(input) {
+                    Ok(_) => Ok(()),
+                    Err(e) => Err(format!("Invalid URL: {}", e)),
+                })
+                .build(),
+            Question::password("secret")
+                .message("Secret of Clash API, default to None:")
+                .build(),
+        ];
+        if self.name.is_none() {
+            questions.push(
+                Question::input("name")
+                    .message("Name of the server, default to None:")
+                    .build(),
+            );
+        }
+        let mut res = prompt(questions)?;
+        debug!("{:#?}", res);
+        let mut optional = |key: &str| match res.remove(key).and_then(|x| x.try_into_string().ok())
+        {
+            Some(string) if string.is_empty() => None,
+            string => string,
+        };
+        let secret = optional("secret");
+        let name = optional("name").or_else(|| self.name.clone());
+
+        let url_str = res.remove("url").unwrap().try_into_string().unwrap();
+        let url = Url::parse(&url_str).unwrap();
+
+        Ok(Server {
+            name,
+            ..Server::new(url, secret)
+        })
+    }
+
+    /// Find the API in config files of Clash, and take the first one that
+    /// responds to `get_version`
+    fn detect(&self, timeout: Duration) -> Result<Server> {
+        let paths = match self.from {
+            Some(ref path) => vec![path.to_owned()],
+            None => {
+                let home = home::home_dir().unwrap_or_default();
+                DETECT_PATHS
+                    .iter()
+                    .map(|path| home.join(path))
+                    .filter(|path| path.is_file())
+                    .collect()
+            }
+        };
+        if paths.is_empty() {
+            return Err(ErrorKind::DetectFailed(
+                "common paths, use `--from` to point to the config of Clash".to_owned(),
+            )
+            .into());
+        }
+
+        for path in &paths {
+            debug!("Detecting from {}", path.display());
+            let profile = match fs::read_to_string(path) {
+                Ok(content) => Profile::parse(&content),
+                Err(e) => {
+                    warn!("Cannot read {} ({})", path.display(), e);
+                    continue;
+                }
+            };
+            let url = match profile.external_controller() {
+                Ok(Some(url)) => url,
+                Ok(None) => {
+                    warn!("No `external-controller` in {}", path.display());
+                    continue;
+                }
+                Err(e) => {
+                    warn!("{} in {}", e, path.display());
+                    continue;
+                }
+            };
+            let server = Server {
+                name: self.name.clone(),
+                ..Server::new(url, profile.secret().map(ToOwned::to_owned))
+            };
+            match Clash::builder(server.url.clone())
+                .map(|x| {
+                    x.secret(server.secret.clone())
+                        .timeout(Some(timeout))
+                        .build()
+                })
+                .and_then(|clash| clash.get_version())
+            {
+                Ok(version) => {
+                    info!(
+                        "Found {} {} at {} from {}",
+                        version.flavor(),
+                        version.version.to_string(),
+                        server.url,
+                        path.display()
+                    );
+                    return Ok(server);
+                }
+                Err(e) => warn!(
+                    "{} from {} is not usable ({})",
+                    server.url,
+                    path.display(),
+                    e
+                ),
+            }
+        }
+
+        let paths = paths
+            .iter()
+            .map(|x| x.display().to_string())
+            .collect::<Vec<_>>();
+        Err(ErrorKind::DetectFailed(paths.join(", ")).into())
+    }
 }
 
 impl ServerSubcommand {
     pub fn handle(&self, flags: &Flags) -> Result<()> {
+        if let Self::Check = self {
+            return check_servers(flags);
+        }
         let mut config = flags.get_config()?;
 
-        match self {
-            Self::Add => {
-                let questions = [
-                    Question::input("url")
-                        .message("URL of Clash API")
-                        .validate(|input, _| match Url::parse(input) {
-                            Ok(_) => Ok(()),
-                            Err(e) => Err(format!("Invalid URL: {}", e)),
-                        })
-                        .build(),
-                    Question::password("secret")
-                        .message("Secret of Clash API, default to None:")
-                        .build(),
-                ];
-                let mut res = prompt(questions).expect("Error during prompt");
-                debug!("{:#?}", res);
-                let secret = match res.remove("secret").unwrap().try_into_string().unwrap() {
-                    string if string == *"" => None,
-                    secret => Some(secret),
-                };
-
-                let url_str = res.remove("url").unwrap().try_into_string().unwrap();
-                let url = Url::parse(&url_str).unwrap();
+        if let Self::Export(opt) = self {
+            let servers = config
+                .servers
+                .iter()
+                .cloned()
+                .map(|mut server| {
+                    if !opt.with_secrets {
+                        server.secret = None;
+                    }
+                    server
+                })
+                .collect();
+            return flags.output.print(&ServerExport(servers));
+        }
+        if let Self::Import(opt) = self {
+            return import_servers(opt, &mut config, flags);
+        }
 
-                let server = Server {
-                    secret,
-                    url: url.clone(),
+        match self {
+            Self::Add(opt) => {
+                let server = match opt.url {
+                    _ if opt.detect => opt.detect(Duration::from_millis(flags.timeout(None)))?,
+                    Some(ref url) => Server {
+                        name: opt.name.clone(),
+                        secret_env: opt.secret_env.clone(),
+                        secret_file: opt.secret_file.clone(),
+                        secret_cmd: opt.secret_cmd.clone(),
+                        ..Server::new(url.clone(), opt.secret.clone())
+                    },
+                    None => opt.prompt()?,
                 };
+                let url = server.url.clone();
+                let added = server.to_string();
 
-                info!("Adding {}", server);
-
-                config.servers.push(server);
-                debug!("{:#?}", config.servers);
-                config.use_server(url)?;
-                config.write()?;
+                config.update(|config| {
+                    config.add_server(server)?;
+                    debug!("{:#?}", config.servers);
+                    config.use_server(url)
+                })?;
+                info!("Added {}, and using it", added);
             }
-            Self::Use => {
+            Self::Use(opt) => {
                 if config.servers.is_empty() {
                     warn!("No server configured yet. Use `clashctl server add` first.");
                     return Ok(());
                 }
-                let servers = config.servers.iter().map(|server| server.url.as_str());
-                let ans = &prompt_one(
-                    Question::select("server")
-                        .message("Select active server to interact with")
-                        .choices(servers)
-                        .build(),
-                )?;
-                let ans_str = &ans.as_list_item().unwrap().text;
-                config.use_server(Url::parse(ans_str).unwrap())?;
-                config.write()?;
+                let url = match opt.server {
+                    Some(ref query) => config.find_server(query)?.url.clone(),
+                    None => {
+                        let servers = config.servers.iter().map(describe);
+                        let ans = &prompt_one(
+                            Question::select("server")
+                                .message("Select active server to interact with")
+                                .choices(servers)
+                                .build(),
+                        )?;
+                        config.servers[ans.as_list_item().unwrap().index]
+                            .url
+                            .clone()
+                    }
+                };
+                config.update(|config| config.use_server(url))?;
+                if let Some(server) = config.using_server() {
+                    info!("Using {}", server);
+                }
             }
             Self::List => {
                 if config.servers.is_empty() {
                     warn!("No server configured yet. Use `clashctl server add` first.");
-                    return Ok(());
                 }
-                let (Width(terminal_width), _) = terminal_size().unwrap_or((Width(70), Height(0)));
-                let active = config.using_server();
-                println!("\n{:-<1$}", "", terminal_width as usize);
-                println!("{:<8}{:<50}", "ACTIVE".green(), "URL");
-                println!("{:-<1$}", "", terminal_width as usize);
-                for server in &config.servers {
-                    let is_active = match active {
-                        Some(active) => server == active,
-                        _ => false,
-                    };
-                    println!(
-                        "{:^8}{:<50}",
-                        if is_active { "→".green() } else { "".green() },
-                        server.url.as_str(),
-                    )
+                return flags.output.print(&ServerList::new(&config));
+            }
+            Self::Del(opt) if !opt.servers.is_empty() => {
+                // Find all of them first, so that nothing is removed on typos
+                let removed = config.update(|config| {
+                    for query in &opt.servers {
+                        config.find_server(query)?;
+                    }
+                    opt.servers
+                        .iter()
+                        .map(|query| config.remove_server(query))
+                        .collect::<InteractiveResult<Vec<_>>>()
+                })?;
+                for server in removed {
+                    info!("Removed {}", server);
                 }
-                println!("{:-<1$}\n", "", terminal_width as usize);
             }
-            Self::Del => {
+            Self::Del(_) => {
                 if config.servers.is_empty() {
                     warn!("No server configured yet. Use `clashctl server add` first.");
                     return Ok(());
                 }
-                let servers = config.servers.iter().map(|server| server.url.as_str());
+                let servers = config.servers.iter().map(describe);
                 let ans = &prompt([
                     Question::multi_select("server")
                         .message("Select server(s) to remove")
@@ -142,18 +410,384 @@ impl ServerSubcommand {
                         warn!("Operation cancelled")
                     }
                     (Some(servers), Some(true)) => {
-                        info!("Removing {} servers", servers.len());
-                        let server_names =
-                            servers.iter().map(|x| x.text.clone()).collect::<Vec<_>>();
-                        config
-                            .servers
-                            .retain(|f| !server_names.contains(&f.url.to_string()))
+                        let urls = servers
+                            .iter()
+                            .map(|x| config.servers[x.index].url.to_string())
+                            .collect::<Vec<_>>();
+                        let removed = config.update(|config| {
+                            let removed = urls
+                                .iter()
+                                .map(|url| config.remove_server(url))
+                                .collect::<InteractiveResult<Vec<_>>>()?;
+                            debug!("{:#?}", config.servers);
+                            Ok(removed)
+                        })?;
+                        for server in removed {
+                            info!("Removed {}", server);
+                        }
                     }
                 }
-                debug!("{:#?}", config.servers);
-                config.write()?;
             }
+            Self::Rename(opt) => {
+                config.update(|config| config.rename_server(&opt.server, &opt.name))?;
+                info!("Renamed `{}` to `{}`", opt.server, opt.name);
+            }
+            Self::Check | Self::Export(_) | Self::Import(_) => unreachable!(),
         }
         Ok(())
     }
 }
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ServerEntry {
+    pub name: Option<String>,
+    pub url: Url,
+    pub active: bool,
+}
+
+/// Configured servers, printed by `server list`
+#[derive(Serialize, Debug, Clone)]
+#[serde(transparent)]
+pub struct ServerList(Vec<ServerEntry>);
+
+impl ServerList {
+    pub fn new(config: &Config) -> Self {
+        let active = config.using_server();
+        Self(
+            config
+                .servers
+                .iter()
+                .map(|server| ServerEntry {
+                    name: server.name.clone(),
+                    url: server.url.clone(),
+                    active: active.is_some_and(|active| server == active),
+                })
+                .collect(),
+        )
+    }
+}
+
+impl Render for ServerList {
+    fn render_table(&self) {
+        if self.0.is_empty() {
+            return;
+        }
+        let (Width(terminal_width), _) = terminal_size().unwrap_or((Width(70), Height(0)));
+        println!("\n{:-<1$}", "", terminal_width as usize);
+        let width = self
+            .0
+            .iter()
+            .filter_map(|x| x.name.as_ref())
+            .map(|x| x.chars().count())
+            .max()
+            .unwrap_or(0)
+            .max(4)
+            + 2;
+        println!(
+            "{:<8}{:<width$}{:<50}",
+            "ACTIVE".green(),
+            "NAME",
+            "URL",
+            width = width
+        );
+        println!("{:-<1$}", "", terminal_width as usize);
+        for server in &self.0 {
+            println!(
+                "{:^8}{:<width$}{:<50}",
+                if server.active {
+                    "→".green()
+                } else {
+                    "".green()
+                },
+                server.name.as_deref().unwrap_or("-"),
+                server.url.as_str(),
+                width = width
+            )
+        }
+        println!("{:-<1$}\n", "", terminal_width as usize);
+    }
+}
+
+/// Result of checking a server
+#[derive(Serialize, Debug, Clone)]
+pub struct ServerCheck {
+    pub name: Option<String>,
+    pub url: Url,
+    pub reachable: bool,
+    /// Whether the secret is accepted, unknown if not reachable
+    pub auth: Option<bool>,
+    pub version: Option<String>,
+    pub flavor: Option<String>,
+    /// Round trip of `GET /version`, in ms
+    pub latency: Option<u64>,
+    /// Whether the traffic stream sends its first sample
+    pub traffic: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl ServerCheck {
+    pub fn run(server: &Server, timeout: Duration) -> Self {
+        let mut check = Self {
+            name: server.name.clone(),
+            url: server.url.clone(),
+            reachable: false,
+            auth: None,
+            version: None,
+            flavor: None,
+            latency: None,
+            traffic: None,
+            error: None,
+        };
+        let clash = match server.clone().into_clash_with_timeout(Some(timeout)) {
+            Ok(clash) => clash,
+            Err(e) => {
+                check.error = Some(e.to_string());
+                return check;
+            }
+        };
+
+        let start = Instant::now();
+        match clash.get_version() {
+            Ok(version) => {
+                check.latency = Some(start.elapsed().as_millis() as u64);
+                check.reachable = true;
+                check.auth = Some(true);
+                check.version = Some(version.version.to_string());
+                check.flavor = Some(version.flavor().to_owned());
+            }
+            Err(e) => {
+                match e.status() {
+                    Some(401 | 403) => {
+                        check.reachable = true;
+                        check.auth = Some(false);
+                        check.error = Some("Secret is not accepted by the server".to_owned());
+                    }
+                    Some(_) => check.reachable = true,
+                    None => {}
+                }
+                check.error.get_or_insert_with(|| e.to_string());
+                return check;
+            }
+        }
+
+        // Clash sends traffic once per second
+        let traffic = traffic_opens(clash, timeout + Duration::from_secs(1));
+        if !traffic {
+            check.error = Some("Traffic stream did not send anything".to_owned());
+        }
+        check.traffic = Some(traffic);
+        check
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.auth == Some(true) && self.traffic == Some(true)
+    }
+}
+
+/// Whether the first sample of traffic arrives in `timeout`. Reading the
+/// stream blocks, so it's read in another thread.
+fn traffic_opens(clash: Clash, timeout: Duration) -> bool {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let opened = clash
+            .get_traffic()
+            .ok()
+            .and_then(|mut traffic| traffic.next())
+            .is_some_and(|x| x.is_ok());
+        let _ = tx.send(opened);
+    });
+    rx.recv_timeout(timeout).unwrap_or(false)
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(transparent)]
+pub struct ServerChecks(pub Vec<ServerCheck>);
+
+impl Render for ServerChecks {
+    fn render_table(&self) {
+        // Padded before colored, as colors break the padding
+        let yes_no = |x: Option<bool>, width: usize| match x {
+            Some(true) => format!("{:<1$}", "yes", width).green().to_string(),
+            Some(false) => format!("{:<1$}", "no", width).red().to_string(),
+            None => format!("{:<1$}", "-", width).dimmed().to_string(),
+        };
+        let name_width = self
+            .0
+            .iter()
+            .filter_map(|x| x.name.as_ref())
+            .map(|x| x.chars().count())
+            .max()
+            .unwrap_or(0)
+            .max(4)
+            + 2;
+        println!(
+            "{:<name_width$}{:<32}{:<11}{:<8}{:<24}{:<9}TRAFFIC",
+            "NAME",
+            "URL",
+            "REACHABLE",
+            "AUTH",
+            "VERSION",
+            "LATENCY",
+            name_width = name_width
+        );
+        for check in &self.0 {
+            let version = match (&check.flavor, &check.version) {
+                (Some(flavor), Some(version)) => format!("{} {}", flavor, version),
+                _ => "-".to_owned(),
+            };
+            let latency = check
+                .latency
+                .map_or_else(|| "-".to_owned(), |x| format!("{}ms", x));
+            println!(
+                "{:<name_width$}{:<32}{}{}{:<24}{:<9}{}",
+                check.name.as_deref().unwrap_or("-"),
+                check.url.as_str(),
+                yes_no(Some(check.reachable), 11),
+                yes_no(check.auth, 8),
+                version,
+                latency,
+                yes_no(check.traffic, 0),
+                name_width = name_width
+            );
+            if let Some(ref error) = check.error {
+                println!(
+                    "{:<name_width$}{}",
+                    "",
+                    error.red(),
+                    name_width = name_width
+                );
+            }
+        }
+    }
+}
+
+/// Servers to check. Only the one given by `--server` or `CLASHCTL_URL` if
+/// set, and all configured servers otherwise.
+fn servers_to_check(flags: &Flags) -> Result<Vec<Server>> {
+    if flags.server.is_some() || env::var_os(URL_ENV).is_some() {
+        return Ok(flags.get_server()?.into_iter().collect());
+    }
+    Ok(flags.get_config()?.servers.clone())
+}
+
+fn check_servers(flags: &Flags) -> Result<()> {
+    let servers = servers_to_check(flags)?;
+    if servers.is_empty() {
+        warn!("No server configured yet. Use `clashctl server add` first.");
+        return Ok(());
+    }
+    let checks = servers
+        .par_iter()
+        .map(|server| ServerCheck::run(server, Duration::from_millis(flags.timeout(Some(server)))))
+        .collect::<Vec<_>>();
+    let failed = checks.iter().filter(|x| !x.is_ok()).count();
+    flags.output.print(&ServerChecks(checks))?;
+    match failed {
+        0 => Ok(()),
+        failed => Err(ErrorKind::CheckFailed(failed).into()),
+    }
+}
+
+/// Servers printed by `server export`, in YAML unless another output format
+/// is given
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(transparent)]
+pub struct ServerExport(pub Vec<Server>);
+
+impl Render for ServerExport {
+    fn render_table(&self) {
+        match serde_yaml::to_string(self) {
+            Ok(yaml) => print!("{}", yaml),
+            Err(e) => warn!("Cannot export servers ({})", e),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(transparent)]
+pub struct ImportReport(pub Vec<ImportEntry>);
+
+impl Render for ImportReport {
+    fn render_table(&self) {
+        let name_width = self
+            .0
+            .iter()
+            .filter_map(|x| x.name.as_ref())
+            .map(|x| x.chars().count())
+            .max()
+            .unwrap_or(0)
+            .max(4)
+            + 2;
+        println!(
+            "{:<name_width$}{:<32}{:<11}DETAIL",
+            "NAME",
+            "URL",
+            "ACTION",
+            name_width = name_width
+        );
+        for entry in &self.0 {
+            let action = format!("{:<11}", format!("{:?}", entry.action).to_lowercase());
+            let action = match entry.action {
+                ImportAction::Added | ImportAction::Updated => action.green().to_string(),
+                ImportAction::Unchanged => action.dimmed().to_string(),
+                ImportAction::Conflict => action.yellow().to_string(),
+                ImportAction::Invalid => action.red().to_string(),
+            };
+            println!(
+                "{:<name_width$}{:<32}{}{}",
+                entry.name.as_deref().unwrap_or("-"),
+                entry.url.as_str(),
+                action,
+                entry.detail.as_deref().unwrap_or(""),
+                name_width = name_width
+            )
+        }
+    }
+}
+
+fn import_servers(opt: &ServerImportOpt, config: &mut Config, flags: &Flags) -> Result<()> {
+    let content = if opt.file.as_os_str() == "-" {
+        let mut content = String::new();
+        io::stdin()
+            .read_to_string(&mut content)
+            .map_err(ErrorKind::ImportError)?;
+        content
+    } else {
+        fs::read_to_string(&opt.file).map_err(ErrorKind::ImportError)?
+    };
+    // YAML is a superset of JSON
+    let ServerExport(servers) = serde_yaml::from_str(&content)?;
+
+    let import = |config: &mut Config| {
+        let report = config.import_servers(servers, opt.overwrite);
+        if config.using_server().is_none() {
+            if let Some(entry) = report.iter().find(|x| x.action == ImportAction::Added) {
+                config.use_server(entry.url.clone())?;
+            }
+        }
+        Ok(report)
+    };
+    let report = if opt.dry_run {
+        info!("Dry run, config is not changed");
+        import(config)?
+    } else {
+        config.update(import)?
+    };
+
+    let count = |action| report.iter().filter(|x| x.action == action).count();
+    let (added, updated, conflicts) = (
+        count(ImportAction::Added),
+        count(ImportAction::Updated),
+        count(ImportAction::Conflict),
+    );
+
+    info!("{} added, {} updated", added, updated);
+    if conflicts > 0 {
+        warn!(
+            "{} conflict(s) skipped, use `--overwrite` to replace existing servers",
+            conflicts
+        );
+    }
+    flags.output.print(&ImportReport(report))
+}
@@ -0,0 +1,71 @@
+use std::path::PathBuf;
+
+use owo_colors::OwoColorize;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{
+    interactive::{Flags, Layer, ValueSource},
+    Render, Result,
+};
+
+/// Layered config of clashctl printed by `sources`
+#[derive(Serialize, Debug, Clone)]
+pub struct ConfigSources {
+    pub system: Option<PathBuf>,
+    pub user: PathBuf,
+    pub values: Vec<ValueSource>,
+}
+
+impl Render for ConfigSources {
+    fn render_table(&self) {
+        let system = self
+            .system
+            .as_ref()
+            .map_or_else(|| "-".to_owned(), |x| x.display().to_string());
+        println!("{:<16}{}", "System config".green(), system);
+        println!("{:<16}{}\n", "User config".green(), self.user.display());
+
+        let display = |value: &Value| match value {
+            Value::Null => "-".to_owned(),
+            Value::String(s) => s.to_owned(),
+            Value::Object(map) => map
+                .values()
+                .map(|x| x.as_str().map_or_else(|| x.to_string(), ToOwned::to_owned))
+                .collect::<Vec<_>>()
+                .join(" "),
+            value => value.to_string(),
+        };
+        let width = self
+            .values
+            .iter()
+            .map(|x| x.key.chars().count())
+            .max()
+            .unwrap_or(0)
+            + 2;
+        println!("{:<width$}{:<40}SOURCE", "KEY", "VALUE", width = width);
+        for value in &self.values {
+            let layer = match value.layer {
+                Layer::Default => "default".dimmed().to_string(),
+                Layer::System => "system".yellow().to_string(),
+                Layer::User => "user".green().to_string(),
+            };
+            println!(
+                "{:<width$}{:<40}{}",
+                value.key,
+                display(&value.value),
+                layer,
+                width = width
+            )
+        }
+    }
+}
+
+pub fn sources(flags: &Flags) -> Result<()> {
+    let config = flags.get_config()?;
+    flags.output.print(&ConfigSources {
+        system: config.system_path().map(ToOwned::to_owned),
+        user: config.path().to_owned(),
+        values: config.sources(),
+    })
+}
@@ -0,0 +1,196 @@
+use std::{
+    sync::mpsc::RecvTimeoutError,
+    time::{Duration, Instant},
+};
+
+use bytesize::ByteSize;
+use chrono::{DateTime, Local};
+use clap::Parser;
+use clashctl_core::model::Traffic;
+use log::{info, warn};
+use owo_colors::OwoColorize;
+use serde::Serialize;
+
+use crate::{interactive::Flags, spawn_stream, Render, Result, StreamEvent};
+
+#[derive(Parser, Debug, Clone)]
+pub struct TrafficOpt {
+    #[clap(
+        short = 'n',
+        long,
+        conflicts_with = "duration",
+        help = "Stop after this many samples, one per second"
+    )]
+    pub count: Option<usize>,
+
+    #[clap(
+        short,
+        long,
+        parse(try_from_str = humantime::parse_duration),
+        help = "Stop after this long, e.g. `30s` or `5m`"
+    )]
+    pub duration: Option<Duration>,
+
+    #[clap(
+        long,
+        help = "Only print the summary when stopped. Without it, the summary is only printed in \
+                table output, so that other formats are one kind of record"
+    )]
+    pub summary: bool,
+}
+
+/// A sample of traffic, in bytes per second
+#[derive(Serialize, Debug, Clone)]
+pub struct TrafficRecord {
+    pub time: DateTime<Local>,
+    pub up: u64,
+    pub down: u64,
+}
+
+impl Render for TrafficRecord {
+    fn render_table(&self) {
+        println!(
+            "{} {} {:<14}{} {}",
+            self.time.format("%T").to_string().dimmed(),
+            "▲".dimmed(),
+            ByteSize(self.up).to_string_as(true) + "/s",
+            "▼".dimmed(),
+            ByteSize(self.down).to_string_as(true) + "/s",
+        )
+    }
+}
+
+/// Statistics of traffic in one direction. `total` is in bytes, and others
+/// are in bytes per second.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DirectionStats {
+    pub total: u64,
+    pub peak: u64,
+    pub avg: u64,
+    pub p95: u64,
+}
+
+impl DirectionStats {
+    /// Calculate from per-second rates
+    pub fn from_rates(rates: &[u64]) -> Self {
+        if rates.is_empty() {
+            return Self::default();
+        }
+        let mut sorted = rates.to_vec();
+        sorted.sort_unstable();
+        let total = rates.iter().sum::<u64>();
+        // Nearest-rank percentile
+        let rank = (sorted.len() * 95).div_ceil(100);
+        Self {
+            total,
+            peak: sorted[sorted.len() - 1],
+            avg: total / rates.len() as u64,
+            p95: sorted[rank.max(1) - 1],
+        }
+    }
+}
+
+/// Summary printed by `traffic` when it stops
+#[derive(Serialize, Debug, Clone)]
+pub struct TrafficSummary {
+    pub samples: usize,
+    pub up: DirectionStats,
+    pub down: DirectionStats,
+}
+
+impl Render for TrafficSummary {
+    fn render_table(&self) {
+        let size = |x: u64| ByteSize(x).to_string_as(true);
+        let rate = |x: u64| size(x) + "/s";
+        println!("\n{} samples", self.samples);
+        println!("{:<8}{:<14}{:<14}{:<14}P95", "", "TOTAL", "PEAK", "AVG");
+        for (name, stats) in [("UP", self.up), ("DOWN", self.down)] {
+            println!(
+                "{:<8}{:<14}{:<14}{:<14}{}",
+                name,
+                size(stats.total),
+                rate(stats.peak),
+                rate(stats.avg),
+                rate(stats.p95)
+            )
+        }
+    }
+}
+
+impl TrafficOpt {
+    pub fn handle(&self, flags: &Flags) -> Result<()> {
+        let server = match flags.get_server()? {
+            Some(server) => server,
+            None => {
+                warn!("No server configured yet. Use `clashctl server add` first.");
+                return Ok(());
+            }
+        };
+        info!("Using {}", server);
+        let clash = flags.connect(&server)?;
+
+        let events = spawn_stream(clash.get_traffic()?)?;
+        let deadline = self.duration.map(|x| Instant::now() + x);
+        let (mut up, mut down) = (vec![], vec![]);
+
+        let result = loop {
+            if self.count.is_some_and(|x| up.len() >= x) {
+                break Ok(());
+            }
+            let event = match deadline {
+                Some(deadline) => {
+                    match events.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                        Ok(event) => event,
+                        Err(RecvTimeoutError::Timeout) => break Ok(()),
+                        Err(RecvTimeoutError::Disconnected) => StreamEvent::Closed(None),
+                    }
+                }
+                None => events.recv().unwrap_or(StreamEvent::Closed(None)),
+            };
+            let Traffic {
+                up: up_rate,
+                down: down_rate,
+            } = match event {
+                StreamEvent::Item(traffic) => traffic,
+                StreamEvent::Closed(Some(e)) => break Err(e.into()),
+                StreamEvent::Closed(None) => {
+                    warn!("Traffic stream closed by server");
+                    break Ok(());
+                }
+                StreamEvent::Interrupted => break Ok(()),
+            };
+            up.push(up_rate);
+            down.push(down_rate);
+            if !self.summary {
+                flags.output.print(&TrafficRecord {
+                    time: Local::now(),
+                    up: up_rate,
+                    down: down_rate,
+                })?;
+            }
+        };
+
+        if !up.is_empty() && (self.summary || flags.output.is_table()) {
+            flags.output.print(&TrafficSummary {
+                samples: up.len(),
+                up: DirectionStats::from_rates(&up),
+                down: DirectionStats::from_rates(&down),
+            })?;
+        }
+        result
+    }
+}
+
+#[test]
+fn test_direction_stats() {
+    let rates = (1..=20).map(|x| x * 100).collect::<Vec<_>>();
+    let stats = DirectionStats::from_rates(&rates);
+    assert_eq!(stats.total, 21000);
+    assert_eq!(stats.peak, 2000);
+    assert_eq!(stats.avg, 1050);
+    assert_eq!(stats.p95, 1900);
+
+    let stats = DirectionStats::from_rates(&[42]);
+    assert_eq!(stats.p95, 42);
+    assert_eq!(DirectionStats::from_rates(&[]), DirectionStats::default());
+}
@@ -0,0 +1,188 @@
+use clashctl_core::{model::ProxyType, Clash};
+use log::debug;
+use owo_colors::OwoColorize;
+use rayon::prelude::*;
+use serde::Serialize;
+use terminal_size::{terminal_size, Height, Width};
+
+use crate::{Render, Result};
+
+/// Parameters of a latency test over multiple proxies
+#[derive(Debug, Clone)]
+pub struct DelayTest {
+    pub url: String,
+    /// Timeout of each test, in ms
+    pub timeout: u64,
+    pub rounds: usize,
+    /// Maximum number of tests running at the same time
+    pub concurrency: usize,
+}
+
+impl DelayTest {
+    /// Test every proxy in `targets` for [`DelayTest::rounds`] rounds. Failed
+    /// tests are counted instead of returned as errors.
+    pub fn run(&self, clash: &Clash, targets: &[(String, ProxyType)]) -> Result<Vec<DelayStats>> {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(self.concurrency.max(1))
+            .build()?;
+
+        let mut samples = vec![Vec::with_capacity(self.rounds); targets.len()];
+        for round in 1..=self.rounds {
+            debug!("Testing {} proxies, round {}", targets.len(), round);
+            let result = pool.install(|| {
+                targets
+                    .par_iter()
+                    .map(|(name, _)| {
+                        clash
+                            .get_proxy_delay(name, &self.url, self.timeout)
+                            .map_err(|e| debug!("Failed to test {}: {}", name, e))
+                            .ok()
+                            .map(|x| x.delay)
+                            // Clash reports 0 when the test fails
+                            .filter(|&delay| delay != 0)
+                    })
+                    .collect::<Vec<_>>()
+            });
+            samples
+                .iter_mut()
+                .zip(result)
+                .for_each(|(samples, x)| samples.push(x));
+        }
+
+        Ok(targets
+            .iter()
+            .zip(samples)
+            .map(|((name, proxy_type), samples)| {
+                DelayStats::from_samples(name, *proxy_type, &samples)
+            })
+            .collect())
+    }
+}
+
+/// Latency statistics of one proxy over several rounds, in ms. Statistics
+/// are `None` when every round failed.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct DelayStats {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub proxy_type: ProxyType,
+    pub min: Option<u64>,
+    pub avg: Option<u64>,
+    pub max: Option<u64>,
+    /// Mean difference between consecutive successful rounds
+    pub jitter: Option<u64>,
+    pub failures: usize,
+    pub rounds: usize,
+}
+
+impl DelayStats {
+    pub fn from_samples(name: &str, proxy_type: ProxyType, samples: &[Option<u64>]) -> Self {
+        let ok = samples.iter().flatten().copied().collect::<Vec<_>>();
+        let avg = (!ok.is_empty()).then(|| ok.iter().sum::<u64>() / ok.len() as u64);
+        let jitter = (ok.len() > 1).then(|| {
+            ok.windows(2).map(|x| x[0].abs_diff(x[1])).sum::<u64>() / (ok.len() - 1) as u64
+        });
+        Self {
+            name: name.to_owned(),
+            proxy_type,
+            min: ok.iter().min().copied(),
+            avg,
+            max: ok.iter().max().copied(),
+            jitter,
+            failures: samples.len() - ok.len(),
+            rounds: samples.len(),
+        }
+    }
+
+    /// Whether at least one round succeeded
+    #[inline]
+    pub fn is_alive(&self) -> bool {
+        self.avg.is_some()
+    }
+}
+
+/// Pick the alive proxy with the lowest average delay, which should also be
+/// no higher than `max_delay` when given. Ties are broken by jitter, and then
+/// by failures.
+pub fn pick_best(stats: &[DelayStats], max_delay: Option<u64>) -> Option<&DelayStats> {
+    stats
+        .iter()
+        .filter(|x| match (x.avg, max_delay) {
+            (Some(avg), Some(max)) => avg <= max,
+            (avg, None) => avg.is_some(),
+            (None, _) => false,
+        })
+        .min_by_key(|x| (x.avg, x.jitter, x.failures))
+}
+
+/// Result of `proxy test`
+#[derive(Serialize, Debug, Clone)]
+#[serde(transparent)]
+pub struct DelayReport(pub Vec<DelayStats>);
+
+impl Render for DelayReport {
+    fn render_table(&self) {
+        let (Width(terminal_width), _) = terminal_size().unwrap_or((Width(70), Height(0)));
+        let ms = |x: Option<u64>| x.map_or_else(|| "-".to_owned(), |x| x.to_string());
+
+        println!("\n{:-<1$}", "", terminal_width as usize);
+        println!(
+            "{:<14}{:<7}{:<7}{:<7}{:<8}{:<6}NAME",
+            "TYPE", "MIN", "AVG", "MAX", "JITTER", "FAIL"
+        );
+        println!("{:-<1$}", "", terminal_width as usize);
+        for stats in &self.0 {
+            // Pad before coloring, as escape codes break alignment
+            let failures = format!("{:<6}", format!("{}/{}", stats.failures, stats.rounds));
+            println!(
+                "{}{:<7}{:<7}{:<7}{:<8}{}{}",
+                format!("{:<14}", stats.proxy_type).green(),
+                ms(stats.min),
+                ms(stats.avg),
+                ms(stats.max),
+                ms(stats.jitter),
+                if stats.failures == 0 {
+                    failures
+                } else {
+                    failures.red().to_string()
+                },
+                stats.name
+            )
+        }
+        println!("{:-<1$}", "", terminal_width as usize);
+    }
+}
+
+#[test]
+fn test_pick_best() {
+    let stats = [
+        DelayStats::from_samples("A", ProxyType::Vmess, &[Some(100), Some(120)]),
+        DelayStats::from_samples("B", ProxyType::Vmess, &[Some(90), Some(130)]),
+        DelayStats::from_samples("C", ProxyType::Vmess, &[None, None]),
+        DelayStats::from_samples("D", ProxyType::Vmess, &[Some(110), Some(110)]),
+    ];
+    assert_eq!(pick_best(&stats, None).unwrap().name, "D");
+    assert_eq!(pick_best(&stats[..2], None).unwrap().name, "A");
+    assert!(pick_best(&stats, Some(100)).is_none());
+    assert!(pick_best(&stats[2..3], None).is_none());
+}
+
+#[test]
+fn test_delay_stats() {
+    let stats = DelayStats::from_samples(
+        "HK",
+        ProxyType::Vmess,
+        &[Some(100), None, Some(140), Some(120)],
+    );
+    assert_eq!(stats.min, Some(100));
+    assert_eq!(stats.avg, Some(120));
+    assert_eq!(stats.max, Some(140));
+    assert_eq!(stats.jitter, Some(30));
+    assert_eq!(stats.failures, 1);
+    assert_eq!(stats.rounds, 4);
+
+    let dead = DelayStats::from_samples("JP", ProxyType::Vmess, &[None, None]);
+    assert!(!dead.is_alive());
+    assert_eq!(dead.jitter, None);
+    assert_eq!(dead.failures, 2);
+}
@@ -11,6 +11,49 @@ pub enum ErrorKind {
 
     #[error("Requestty error")]
     RequesttyError(#[from] requestty::ErrorKind),
+
+    #[error("Cannot render output ({0})")]
+    JsonOutputError(#[from] serde_json::Error),
+
+    #[error("Cannot render output ({0})")]
+    YamlOutputError(#[from] serde_yaml::Error),
+
+    #[error("Cannot start worker threads ({0})")]
+    ThreadPoolError(#[from] rayon::ThreadPoolBuildError),
+
+    #[error("Invalid config `{0}`: {1}")]
+    InvalidConfig(String, String),
+
+    #[error("Cannot write output ({0})")]
+    OutputError(std::io::Error),
+
+    #[error("Cannot read servers to import ({0})")]
+    ImportError(std::io::Error),
+
+    #[error("No usable Clash found from {0}")]
+    DetectFailed(String),
+
+    #[error("Cannot write log file ({0})")]
+    LogFileError(std::io::Error),
+
+    #[error("Cannot handle Ctrl-C ({0})")]
+    CtrlcError(#[from] ctrlc::Error),
+
+    #[error("Confirmation is required but stdin is not a terminal, use `--yes` to skip it")]
+    ConfirmationRequired,
+
+    #[error("{0} check(s) failed")]
+    CheckFailed(usize),
+
+    #[error("No {kind} matches `{name}`")]
+    NotFound { kind: &'static str, name: String },
+
+    #[error("`{name}` matches more than one {kind}: {candidates}")]
+    Ambiguous {
+        kind: &'static str,
+        name: String,
+        candidates: String,
+    },
 }
 #[derive(Debug, thiserror::Error)]
 #[error(transparent)]
@@ -25,4 +68,31 @@ where
     }
 }
 
+impl Error {
+    pub fn not_found(kind: &'static str, name: impl Into<String>) -> Self {
+        ErrorKind::NotFound {
+            kind,
+            name: name.into(),
+        }
+        .into()
+    }
+
+    pub fn ambiguous<I, S>(kind: &'static str, name: impl Into<String>, candidates: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        ErrorKind::Ambiguous {
+            kind,
+            name: name.into(),
+            candidates: candidates
+                .into_iter()
+                .map(|x| x.as_ref().to_owned())
+                .collect::<Vec<_>>()
+                .join(", "),
+        }
+        .into()
+    }
+}
+
 pub type Result<T> = std::result::Result<T, Error>;
@@ -1,27 +1,124 @@
 use std::{
+    env,
     fmt::Display,
-    fs::{File, OpenOptions},
-    io::{Read, Seek, SeekFrom, Write},
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Write},
     ops::{Deref, DerefMut},
-    path::Path,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
     time::Duration,
 };
 
 use clashctl_core::{Clash, ClashBuilder};
-use log::{debug, info};
+use log::{debug, warn};
 use ron::{from_str, ser::PrettyConfig};
 use serde::{Deserialize, Serialize};
 use url::Url;
 
-use super::{ConfigData, InteractiveError, InteractiveResult};
+use super::{
+    ConfigData, ConfigLayer, InteractiveError, InteractiveResult, RefreshIntervals, ValueSource,
+};
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
 pub struct Server {
     pub url: url::Url,
     pub secret: Option<String>,
+    /// Name to refer to the server by, in place of its URL
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Read secret from this environment variable
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret_env: Option<String>,
+    /// Read secret from this file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret_file: Option<PathBuf>,
+    /// Read secret from output of this command, e.g. `pass show clash/gw`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret_cmd: Option<String>,
+    /// Timeout of requests in ms, in place of the default of `--timeout`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+    /// URL to test latency against, in place of the default of `--test-url`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub test_url: Option<Url>,
+    /// Intervals of refreshing TUI and `conn watch`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refresh: Option<RefreshIntervals>,
+    /// Refuse to change anything on the server, e.g. selected proxies
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub read_only: bool,
 }
 
+#[cfg(test)]
 impl Server {
+    /// Server for tests, parsing `url` and taking optional name and secret
+    pub fn test(url: &str, name: Option<&str>, secret: Option<&str>) -> Self {
+        Self {
+            name: name.map(ToOwned::to_owned),
+            ..Self::new(Url::parse(url).unwrap(), secret.map(ToOwned::to_owned))
+        }
+    }
+}
+
+impl Server {
+    /// Server with plain secret, or no secret at all
+    pub fn new(url: Url, secret: Option<String>) -> Self {
+        Self {
+            url,
+            secret,
+            name: None,
+            secret_env: None,
+            secret_file: None,
+            secret_cmd: None,
+            timeout: None,
+            test_url: None,
+            refresh: None,
+            read_only: false,
+        }
+    }
+
+    /// Fail if the server is read-only. Called before changing anything on it
+    pub fn check_writable(&self) -> InteractiveResult<()> {
+        if self.read_only {
+            return Err(InteractiveError::ReadOnlyServer(self.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Get the secret from the first source set, in the order of `secret`,
+    /// `secret_env`, `secret_file` and `secret_cmd`
+    pub fn resolve_secret(&self) -> InteractiveResult<Option<String>> {
+        let error = |reason: String| InteractiveError::SecretError(self.to_string(), reason);
+
+        if let Some(ref secret) = self.secret {
+            return Ok(Some(secret.to_owned()));
+        }
+        if let Some(ref var) = self.secret_env {
+            return env::var(var)
+                .map(Some)
+                .map_err(|e| error(format!("`{}`: {}", var, e)));
+        }
+        if let Some(ref path) = self.secret_file {
+            return fs::read_to_string(path)
+                .map(|x| Some(x.trim_end().to_owned()))
+                .map_err(|e| error(format!("{}: {}", path.display(), e)));
+        }
+        if let Some(ref cmd) = self.secret_cmd {
+            let output = shell_command(cmd)
+                .stdin(Stdio::null())
+                .stderr(Stdio::inherit())
+                .output()
+                .map_err(|e| error(format!("`{}`: {}", cmd, e)))?;
+            if !output.status.success() {
+                return Err(error(format!("`{}` exited with {}", cmd, output.status)));
+            }
+            return String::from_utf8(output.stdout)
+                .map(|x| Some(x.trim_end().to_owned()))
+                .map_err(|e| error(format!("`{}`: {}", cmd, e)));
+        }
+        Ok(None)
+    }
+
     pub fn into_clash_with_timeout(self, timeout: Option<Duration>) -> InteractiveResult<Clash> {
         Ok(self.into_clash_builder()?.timeout(timeout).build())
     }
@@ -31,13 +128,31 @@ impl Server {
     }
 
     pub fn into_clash_builder(self) -> InteractiveResult<ClashBuilder> {
-        Ok(ClashBuilder::new(self.url)?.secret(self.secret))
+        let secret = self.resolve_secret()?;
+        Ok(ClashBuilder::new(self.url)?.secret(secret))
     }
 }
 
+#[cfg(unix)]
+fn shell_command(cmd: &str) -> Command {
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(cmd);
+    command
+}
+
+#[cfg(windows)]
+fn shell_command(cmd: &str) -> Command {
+    let mut command = Command::new("cmd");
+    command.arg("/C").arg(cmd);
+    command
+}
+
 impl Display for Server {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Server ({})", self.url)
+        match self.name {
+            Some(ref name) => write!(f, "Server {} ({})", name, self.url),
+            None => write!(f, "Server ({})", self.url),
+        }
     }
 }
 
@@ -57,10 +172,90 @@ impl TryInto<ClashBuilder> for Server {
     }
 }
 
+/// Format of config file, told by its extension. RON by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Ron,
+    Toml,
+}
+
+impl ConfigFormat {
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|x| x.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("toml") => Self::Toml,
+            _ => Self::Ron,
+        }
+    }
+
+    pub fn parse(self, content: &str) -> InteractiveResult<ConfigLayer> {
+        Ok(match self {
+            Self::Ron => from_str(content)?,
+            Self::Toml => toml::from_str(content)?,
+        })
+    }
+
+    pub fn generate(self, data: &ConfigLayer) -> InteractiveResult<String> {
+        Ok(match self {
+            Self::Ron => {
+                let pretty_config = PrettyConfig::default().indentor("  ".to_owned());
+                ron::ser::to_string_pretty(data, pretty_config)?
+            }
+            // Through `toml::Value`, which puts values before tables as TOML
+            // requires
+            Self::Toml => toml::to_string_pretty(&toml::Value::try_from(data)?)?,
+        })
+    }
+}
+
 #[derive(Debug)]
 pub struct Config {
     inner: ConfigData,
-    file: File,
+    /// Config file of the user as last read or written, which tells settings
+    /// set by the user
+    user: ConfigLayer,
+    path: PathBuf,
+    format: ConfigFormat,
+    /// Path and content of system config, if layered under this one
+    system: Option<(PathBuf, ConfigLayer)>,
+}
+
+/// Read and migrate a config file. Users' own config files are warned if
+/// others can read them.
+fn read_data(path: &Path, is_private: bool) -> InteractiveResult<ConfigLayer> {
+    // Reading without the lock is fine too, as files are replaced
+    // atomically. The lock only waits for writes in progress.
+    let _lock = lock(path, false).ok();
+    read_data_unlocked(path, is_private)
+}
+
+fn read_data_unlocked(path: &Path, is_private: bool) -> InteractiveResult<ConfigLayer> {
+    debug!("Reading and parsing config file");
+
+    let mut file = OpenOptions::new()
+        .read(true)
+        .open(path)
+        .map_err(InteractiveError::ConfigFileIoError)?;
+
+    let mut buf = match file.metadata() {
+        Ok(meta) => String::with_capacity(meta.len() as usize),
+        Err(_) => String::new(),
+    };
+
+    file.read_to_string(&mut buf)
+        .map_err(InteractiveError::ConfigFileIoError)?;
+
+    if is_private {
+        warn_if_readable(path, &file);
+    }
+
+    debug!("Raw config:\n{}", buf);
+
+    let inner = ConfigFormat::from_path(path).parse(&buf)?;
+
+    debug!("Content read");
+
+    // Migrated config is saved on next write
+    inner.migrate()
 }
 
 // TODO: use config crate
@@ -70,59 +265,156 @@ impl Config {
 
         debug!("Open config file @ {}", path.display());
 
-        let mut this = if !path.exists() {
-            info!("Config file not exist, creating new one");
-            Self {
-                inner: ConfigData::default(),
-                file: File::create(path).map_err(InteractiveError::ConfigFileIoError)?,
-            }
+        let user = if !path.exists() {
+            debug!("Config file not exist, using default one");
+            ConfigLayer::default()
         } else {
-            debug!("Reading and parsing config file");
+            read_data(path, true)?
+        };
 
-            let mut file = OpenOptions::new()
-                .read(true)
-                .open(path)
-                .map_err(InteractiveError::ConfigFileIoError)?;
+        Ok(Self {
+            inner: user.resolve(),
+            user,
+            path: path.to_owned(),
+            format: ConfigFormat::from_path(path),
+            system: None,
+        })
+    }
+
+    /// Layer this config over the system config at `path`, which is managed
+    /// by admins and never written by clashctl
+    pub fn with_system<P: AsRef<Path>>(mut self, path: P) -> InteractiveResult<Self> {
+        let path = path.as_ref();
 
-            let mut buf = match file.metadata() {
-                Ok(meta) => String::with_capacity(meta.len() as usize),
-                Err(_) => String::new(),
-            };
+        debug!("Open system config file @ {}", path.display());
 
-            file.read_to_string(&mut buf)
-                .map_err(InteractiveError::ConfigFileIoError)?;
+        let system = Self::read_system(path)?;
+        self.inner = ConfigData::layered(&system, &self.user).0;
+        self.system = Some((path.to_owned(), system));
+        Ok(self)
+    }
+
+    /// Read the system config. It's not checked for permissions, as it's
+    /// meant to be read by all users.
+    pub fn read_system(path: &Path) -> InteractiveResult<ConfigLayer> {
+        read_data(path, false)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn system_path(&self) -> Option<&Path> {
+        self.system.as_ref().map(|(path, _)| path.as_path())
+    }
 
-            debug!("Raw config:\n{}", buf);
+    fn system_layer(&self) -> ConfigLayer {
+        self.system
+            .as_ref()
+            .map(|(_, system)| system.to_owned())
+            .unwrap_or_default()
+    }
 
-            let inner = from_str(&buf)?;
+    /// Part of config that belongs to the user, and is written to the user's
+    /// config file
+    fn user_data(&self) -> ConfigLayer {
+        self.inner
+            .user_layer(&self.system_layer().resolve(), &self.user)
+    }
 
-            drop(file);
+    /// Where each value of the config comes from
+    pub fn sources(&self) -> Vec<ValueSource> {
+        ConfigData::layered(&self.system_layer(), &self.user_data()).1
+    }
 
-            debug!("Content read");
+    /// Same as [`ConfigData::remove_server`], but servers of system config
+    /// cannot be removed, as they would be back on next read
+    pub fn remove_server(&mut self, query: &str) -> InteractiveResult<Server> {
+        if let Some((_, ref system)) = self.system {
+            if let Ok(server) = system.resolve().find_server(query) {
+                if self.inner.servers.contains(server) {
+                    return Err(InteractiveError::SystemServer(query.to_owned()));
+                }
+            }
+        }
+        self.inner.remove_server(query)
+    }
 
-            let file = File::create(path).map_err(InteractiveError::ConfigFileIoError)?;
+    /// Remove the preset `name`. Presets of system config cannot be removed,
+    /// but user's presets of the same name can, which brings back the system
+    /// ones. Returns whether the system preset is brought back.
+    pub fn remove_preset(&mut self, name: &str) -> InteractiveResult<bool> {
+        if let Some((_, ref system)) = self.system {
+            if let Some(preset) = system.presets.get(name) {
+                if self.inner.presets.get(name) == Some(preset) {
+                    return Err(InteractiveError::SystemPreset(name.to_owned()));
+                }
+                self.inner
+                    .presets
+                    .insert(name.to_owned(), preset.to_owned());
+                return Ok(true);
+            }
+        }
+        self.inner.presets.remove(name);
+        Ok(false)
+    }
 
-            Self { inner, file }
+    /// Re-read the config file, change it with `f` and write it back, while
+    /// holding the lock all along. Changes made by others since the config
+    /// was read are kept this way, instead of being overwritten by what was
+    /// read before. Nothing is written if `f` fails.
+    pub fn update<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> InteractiveResult<T>,
+    ) -> InteractiveResult<T> {
+        let _lock = lock(&self.path, true).map_err(InteractiveError::ConfigFileIoError)?;
+
+        self.user = if self.path.exists() {
+            read_data_unlocked(&self.path, true)?
+        } else {
+            ConfigLayer::default()
         };
+        self.inner = ConfigData::layered(&self.system_layer(), &self.user).0;
 
-        this.write()?;
-        Ok(this)
+        let ret = f(self)?;
+        self.write_unlocked()?;
+        Ok(ret)
     }
 
+    /// Write the config as is. Use [`Config::update`] to change the config,
+    /// so that changes of others are not overwritten.
     pub fn write(&mut self) -> InteractiveResult<()> {
-        let pretty_config = PrettyConfig::default().indentor("  ".to_owned());
+        let _lock = lock(&self.path, true).map_err(InteractiveError::ConfigFileIoError)?;
+        self.write_unlocked()
+    }
 
-        // Reset the file - Move cursor to 0 and truncate to 0
-        self.file
-            .seek(SeekFrom::Start(0))
-            .and_then(|_| self.file.set_len(0))
-            .map_err(InteractiveError::ConfigFileIoError)?;
+    /// Write to a temporary file and rename it to the config file, so that
+    /// the config file is never left half written. The previous config is
+    /// kept as `<path>.bak` if it's valid.
+    fn write_unlocked(&mut self) -> InteractiveResult<()> {
+        let user = self.user_data();
+        let content = self.format.generate(&user)?;
+
+        let is_valid = |path: &Path| {
+            fs::read_to_string(path)
+                .ok()
+                .is_some_and(|x| self.format.parse(&x).is_ok())
+        };
+        if is_valid(&self.path) {
+            let backup = sibling(&self.path, "bak");
+            create_private(&backup)
+                .and_then(|mut backup| io::copy(&mut File::open(&self.path)?, &mut backup))
+                .map_err(InteractiveError::ConfigFileIoError)?;
+        }
 
-        ron::ser::to_writer_pretty(&mut self.file, &self.inner, pretty_config)?;
-        self.file
-            .flush()
+        let temp = sibling(&self.path, "tmp");
+        let mut file = create_private(&temp).map_err(InteractiveError::ConfigFileIoError)?;
+        file.write_all(content.as_bytes())
+            .and_then(|_| file.sync_all())
+            .and_then(|_| fs::rename(&temp, &self.path))
             .map_err(InteractiveError::ConfigFileIoError)?;
 
+        self.user = user;
         Ok(())
     }
 
@@ -152,6 +444,75 @@ impl Config {
     }
 }
 
+/// `<path>.<extension>`, next to the config file
+pub fn sibling(path: &Path, extension: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".");
+    name.push(extension);
+    name.into()
+}
+
+/// Lock `<path>.lock` for reading or writing the config file. The config file
+/// itself is replaced on each write, so it cannot be locked. The lock is
+/// released when the returned file is dropped.
+///
+/// Only writers create the lock file, so reading fails with `NotFound` when
+/// nothing has been written yet, and never leaves files behind.
+fn lock(path: &Path, exclusive: bool) -> std::io::Result<File> {
+    let file = OpenOptions::new()
+        .read(true)
+        .write(exclusive)
+        .create(exclusive)
+        .truncate(false)
+        .open(sibling(path, "lock"))?;
+    if exclusive {
+        file.lock()?;
+    } else {
+        file.lock_shared()?;
+    }
+    Ok(file)
+}
+
+/// Create `path` in place of the file there, if any. Config may contain
+/// secrets, so only the owner can read it from the moment it exists. Files
+/// left there are removed first, as the mode only applies to new files.
+#[cfg(unix)]
+fn create_private(path: &Path) -> io::Result<File> {
+    use std::os::unix::fs::OpenOptionsExt;
+
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)
+}
+
+#[cfg(not(unix))]
+fn create_private(path: &Path) -> io::Result<File> {
+    File::create(path)
+}
+
+#[cfg(unix)]
+fn warn_if_readable(path: &Path, file: &File) {
+    use std::os::unix::fs::PermissionsExt;
+
+    if let Ok(meta) = file.metadata() {
+        if meta.permissions().mode() & 0o077 != 0 {
+            warn!(
+                "Config file {0} is accessible by other users. Run `chmod 600 {0}` to fix it",
+                path.display()
+            );
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn warn_if_readable(_path: &Path, _file: &File) {}
+
 impl Deref for Config {
     type Target = ConfigData;
 
@@ -176,9 +537,218 @@ fn test_config() {
 
     let mut config = Config::from_dir("/tmp/test.ron").unwrap();
     config.write().unwrap();
-    config.servers.push(Server {
-        url: url::Url::parse(&env::var("PROXY_ADDR").unwrap()).unwrap(),
-        secret: None,
+    config.servers.push(Server::new(
+        url::Url::parse(&env::var("PROXY_ADDR").unwrap()).unwrap(),
+        None,
+    ));
+    config.write().unwrap();
+}
+
+#[test]
+fn test_secret_sources() {
+    let url = Url::parse("http://127.0.0.1:9090").unwrap();
+    let resolve = |f: fn(&mut Server)| {
+        let mut server = Server::new(url.clone(), None);
+        f(&mut server);
+        server.resolve_secret()
+    };
+
+    assert_eq!(resolve(|_| {}).unwrap(), None);
+    assert_eq!(
+        resolve(|x| x.secret = Some("plain".to_owned())).unwrap(),
+        Some("plain".to_owned())
+    );
+
+    env::set_var("CLASHCTL_TEST_SECRET", "from env");
+    assert_eq!(
+        resolve(|x| x.secret_env = Some("CLASHCTL_TEST_SECRET".to_owned())).unwrap(),
+        Some("from env".to_owned())
+    );
+    assert!(resolve(|x| x.secret_env = Some("CLASHCTL_TEST_NO_SECRET".to_owned())).is_err());
+
+    let path = env::temp_dir().join(format!("clashctl-secret-{}", std::process::id()));
+    fs::write(&path, "from file\n").unwrap();
+    let mut server = Server::new(url.clone(), None);
+    server.secret_file = Some(path.clone());
+    assert_eq!(
+        server.resolve_secret().unwrap(),
+        Some("from file".to_owned())
+    );
+    fs::remove_file(path).unwrap();
+
+    #[cfg(unix)]
+    {
+        assert_eq!(
+            resolve(|x| x.secret_cmd = Some("echo from cmd".to_owned())).unwrap(),
+            Some("from cmd".to_owned())
+        );
+        assert!(resolve(|x| x.secret_cmd = Some("exit 1".to_owned())).is_err());
+    }
+}
+
+#[test]
+fn test_config_write() {
+    let dir = env::temp_dir().join(format!("clashctl-config-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("config.ron");
+
+    let mut config = Config::from_dir(&path).unwrap();
+    assert!(!path.exists(), "Reading should not create the file");
+    assert!(!sibling(&path, "lock").exists());
+
+    config.write().unwrap();
+    assert!(!sibling(&path, "bak").exists());
+
+    config.servers.push(Server::new(
+        Url::parse("http://127.0.0.1:9090").unwrap(),
+        None,
+    ));
+    config.write().unwrap();
+    assert!(!sibling(&path, "tmp").exists());
+
+    let backup = Config::from_dir(sibling(&path, "bak")).unwrap();
+    assert!(backup.servers.is_empty());
+    assert_eq!(Config::from_dir(&path).unwrap().servers.len(), 1);
+
+    // Broken config is not backed up
+    fs::write(&path, "(servers: [").unwrap();
+    config.write().unwrap();
+    assert!(Config::from_dir(sibling(&path, "bak"))
+        .unwrap()
+        .servers
+        .is_empty());
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        for path in [path.clone(), sibling(&path, "bak")] {
+            let mode = path.metadata().unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        // Left by a crash, and readable by others
+        let temp = sibling(&path, "tmp");
+        fs::write(&temp, "").unwrap();
+        fs::set_permissions(&temp, fs::Permissions::from_mode(0o644)).unwrap();
+        config.write().unwrap();
+        let mode = path.metadata().unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    // Changes written by others since reading are kept
+    let mut other = Config::from_dir(&path).unwrap();
+    other.servers.clear();
+    other.write().unwrap();
+    let url = Url::parse("http://127.0.0.1:9091").unwrap();
+    config
+        .update(|config| config.add_server(Server::new(url.clone(), None)))
+        .unwrap();
+    let servers = Config::from_dir(&path).unwrap().servers.clone();
+    assert_eq!(servers, [Server::new(url, None)]);
+
+    // Nothing is written on errors
+    let result = config.update(|config| {
+        config.servers.clear();
+        config.remove_server("nope")
     });
+    assert!(result.is_err());
+    assert_eq!(Config::from_dir(&path).unwrap().servers.len(), 1);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_system_presets() {
+    let dir = env::temp_dir().join(format!("clashctl-presets-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let system_path = dir.join("system.ron");
+    let preset = |member: &str| [("Proxy".to_owned(), member.to_owned())].into();
+
+    let mut system = ConfigLayer::default();
+    system.presets.insert("office".to_owned(), preset("HK-1"));
+    fs::write(&system_path, ConfigFormat::Ron.generate(&system).unwrap()).unwrap();
+
+    let mut config = Config::from_dir(dir.join("config.ron"))
+        .unwrap()
+        .with_system(&system_path)
+        .unwrap();
+    assert!(matches!(
+        config.remove_preset("office"),
+        Err(InteractiveError::SystemPreset(_))
+    ));
+
+    // Removing the user's own preset of the name brings back the system one
+    config.presets.insert("office".to_owned(), preset("JP-1"));
+    assert!(config.remove_preset("office").unwrap());
+    assert_eq!(config.presets["office"], preset("HK-1"));
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_config_toml() {
+    let dir = env::temp_dir().join(format!("clashctl-toml-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("config.toml");
+
+    let mut config = Config::from_dir(&path).unwrap();
+    config
+        .add_server(Server {
+            name: Some("local".to_owned()),
+            ..Server::new(Url::parse("http://127.0.0.1:9090").unwrap(), None)
+        })
+        .unwrap();
+    config.using = Some(Url::parse("http://127.0.0.1:9090").unwrap());
     config.write().unwrap();
+
+    let content = fs::read_to_string(&path).unwrap();
+    assert!(content.contains("[[servers]]"), "{}", content);
+
+    let config = Config::from_dir(&path).unwrap();
+    assert_eq!(config.servers.len(), 1);
+    assert!(config.using_server().is_some());
+
+    // Unversioned and minimal
+    fs::write(&path, "servers = []\n").unwrap();
+    assert_eq!(
+        read_data(&path, true).unwrap().version,
+        super::CONFIG_VERSION
+    );
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_server_overrides() {
+    use super::Flags;
+
+    let server: Server = from_str(
+        r#"(
+            url: "http://127.0.0.1:9090",
+            secret: None,
+            timeout: Some(500),
+            refresh: Some((connections: 2000)),
+            read_only: true,
+        )"#,
+    )
+    .unwrap();
+    let refresh = server.refresh.unwrap();
+    assert_eq!(refresh.connections, 2000);
+    assert_eq!(refresh.proxies, RefreshIntervals::default().proxies);
+    assert!(server.check_writable().is_err());
+
+    let flags = Flags::default();
+    assert_eq!(flags.timeout(Some(&server)), 500);
+    assert_eq!(flags.timeout(None), 2000);
+    assert_eq!(
+        flags.test_url(Some(&server), None),
+        flags.test_url(None, None)
+    );
+
+    let flags = Flags {
+        timeout: Some(5000),
+        ..Flags::default()
+    };
+    assert_eq!(flags.timeout(Some(&server)), 5000);
 }
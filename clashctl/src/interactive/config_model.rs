@@ -1,28 +1,952 @@
-use std::path::PathBuf;
+use std::{collections::BTreeMap, fmt::Display, path::PathBuf};
 
-use serde::{Deserialize, Serialize};
+use log::debug;
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use smart_default::SmartDefault;
 use url::Url;
 
-use crate::{ConSort, ProxySort, RuleSort, Server};
+use crate::{ConSort, InteractiveError, InteractiveResult, Preset, ProxySort, RuleSort, Server};
 
-#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+/// Version of config layout written by this build
+pub const CONFIG_VERSION: u32 = 1;
+
+/// `MIGRATIONS[n]` migrates config of version `n` to `n + 1`. Fields added
+/// with `#[serde(default)]` need no migration, only changes of the meaning
+/// or layout of existing fields do.
+const MIGRATIONS: [fn(&mut ConfigLayer); CONFIG_VERSION as usize] = [migrate_v0];
+
+/// Unversioned configs were written with every setting, so ones set to the
+/// default are taken as unset, to not override the system config. They may
+/// also have `using` left pointing to a removed server.
+fn migrate_v0(config: &mut ConfigLayer) {
+    fn unset_default<T: Default + PartialEq>(setting: &mut Option<T>) {
+        if setting.as_ref().is_some_and(|x| *x == T::default()) {
+            *setting = None;
+        }
+    }
+
+    if let Some(Some(ref using)) = config.using {
+        if !config.servers.iter().any(|x| &x.url == using) {
+            config.using = None;
+        }
+    }
+    unset_default(&mut config.using);
+    unset_default(&mut config.tui.log_file);
+    unset_default(&mut config.sort.connections);
+    unset_default(&mut config.sort.rules);
+    unset_default(&mut config.sort.proxies);
+    unset_default(&mut config.profile.path);
+    unset_default(&mut config.profile.reload);
+}
+
+/// Layer of config that a value comes from
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Layer {
+    Default,
+    System,
+    User,
+}
+
+/// A value of the layered config, and where it comes from
+#[derive(Serialize, Debug, Clone)]
+pub struct ValueSource {
+    pub key: String,
+    pub value: serde_json::Value,
+    pub layer: Layer,
+}
+
+/// Layer a setting of user config over the system one. Settings set in a
+/// layer win over lower layers, even if they are set to the default.
+fn layer_field<T>(
+    key: &str,
+    system: &Option<T>,
+    user: &Option<T>,
+    sources: &mut Vec<ValueSource>,
+) -> T
+where
+    T: Default + Clone + Serialize,
+{
+    let (value, layer) = match (user, system) {
+        (Some(value), _) => (value.to_owned(), Layer::User),
+        (None, Some(value)) => (value.to_owned(), Layer::System),
+        (None, None) => (T::default(), Layer::Default),
+    };
+    sources.push(ValueSource {
+        key: key.to_owned(),
+        value: serde_json::to_value(&value).unwrap_or_default(),
+        layer,
+    });
+    value
+}
+
+/// Settings of a layer which is only what the config file has set, with
+/// `None` for ones left out. They are written as plain values, e.g.
+/// `reload: false` rather than `reload: Some(false)`.
+mod present {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<T, S>(value: &Option<T>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Serialize,
+        S: Serializer,
+    {
+        match value {
+            Some(value) => value.serialize(serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+    where
+        T: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        T::deserialize(deserializer).map(Some)
+    }
+}
+
+/// What importing a server did
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportAction {
+    Added,
+    Updated,
+    Unchanged,
+    /// Differs from an existing server, and not overwritten
+    Conflict,
+    Invalid,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ImportEntry {
+    pub name: Option<String>,
+    pub url: Url,
+    pub action: ImportAction,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+/// Fields that differ between two servers
+fn server_diff(a: &Server, b: &Server) -> Vec<&'static str> {
+    [
+        ("url", a.url != b.url),
+        ("name", a.name != b.name),
+        ("secret", a.secret != b.secret),
+        ("secret_env", a.secret_env != b.secret_env),
+        ("secret_file", a.secret_file != b.secret_file),
+        ("secret_cmd", a.secret_cmd != b.secret_cmd),
+        ("timeout", a.timeout != b.timeout),
+        ("test_url", a.test_url != b.test_url),
+        ("refresh", a.refresh != b.refresh),
+        ("read_only", a.read_only != b.read_only),
+    ]
+    .into_iter()
+    .filter_map(|(field, differs)| differs.then_some(field))
+    .collect()
+}
+
+/// Config of clashctl, with layers of config files resolved
+#[derive(Serialize, Debug, Default, Clone)]
 pub struct ConfigData {
     pub servers: Vec<Server>,
     pub using: Option<Url>,
-    #[serde(default)]
     pub tui: TuiConfig,
-    #[serde(default)]
     pub sort: SortsConfig,
+    pub profile: ProfileConfig,
+    /// Latency tests of groups by name, the first matching one is used
+    pub group_tests: Vec<GroupTestConfig>,
+    /// Selections of groups saved by `preset save`, by name of presets
+    pub presets: BTreeMap<String, Preset>,
+}
+
+/// A config file, layered over ones below it into [`ConfigData`]. Settings
+/// left out of the file are `None`, so that ones set to the default still
+/// override lower layers.
+#[derive(Serialize, Deserialize, Debug, SmartDefault, Clone, PartialEq)]
+pub struct ConfigLayer {
+    /// Configs without version are of version 0
+    #[serde(default)]
+    #[default(CONFIG_VERSION)]
+    pub version: u32,
+    #[serde(default)]
+    pub servers: Vec<Server>,
+    #[serde(default, with = "present", skip_serializing_if = "Option::is_none")]
+    pub using: Option<Option<Url>>,
+    #[serde(default, skip_serializing_if = "TuiLayer::is_unset")]
+    pub tui: TuiLayer,
+    #[serde(default, skip_serializing_if = "SortsLayer::is_unset")]
+    pub sort: SortsLayer,
+    #[serde(default, skip_serializing_if = "ProfileLayer::is_unset")]
+    pub profile: ProfileLayer,
+    #[serde(default)]
+    pub group_tests: Vec<GroupTestConfig>,
+    #[serde(default)]
+    pub presets: BTreeMap<String, Preset>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+impl ConfigLayer {
+    /// Migrate config of older versions to the current one, step by step
+    pub fn migrate(mut self) -> InteractiveResult<Self> {
+        if self.version > CONFIG_VERSION {
+            return Err(InteractiveError::ConfigVersionError(self.version));
+        }
+        while self.version < CONFIG_VERSION {
+            debug!(
+                "Migrating config from version {} to {}",
+                self.version,
+                self.version + 1
+            );
+            MIGRATIONS[self.version as usize](&mut self);
+            self.version += 1;
+        }
+        Ok(self)
+    }
+
+    /// Config of this layer alone
+    pub fn resolve(&self) -> ConfigData {
+        ConfigData::layered(&Self::default(), self).0
+    }
+}
+
+impl ConfigData {
+    /// Layer `user` over `system`, along with where each value comes from.
+    /// Servers of both are kept, unless the user has one with the same name
+    /// or URL.
+    pub fn layered(system: &ConfigLayer, user: &ConfigLayer) -> (ConfigData, Vec<ValueSource>) {
+        let mut sources = vec![];
+        let overridden = |server: &Server| {
+            user.servers
+                .iter()
+                .any(|x| x.url == server.url || (x.name.is_some() && x.name == server.name))
+        };
+        let servers = system
+            .servers
+            .iter()
+            .filter(|x| !overridden(x))
+            .map(|x| (x, Layer::System))
+            .chain(user.servers.iter().map(|x| (x, Layer::User)))
+            .map(|(server, layer)| {
+                sources.push(ValueSource {
+                    key: format!(
+                        "servers.{}",
+                        server.name.as_deref().unwrap_or(server.url.as_str())
+                    ),
+                    value: server.url.as_str().into(),
+                    layer,
+                });
+                server.to_owned()
+            })
+            .collect();
+
+        // Tests of the user come first, so they win over the system's
+        let group_tests = user
+            .group_tests
+            .iter()
+            .map(|x| (x, Layer::User))
+            .chain(
+                system
+                    .group_tests
+                    .iter()
+                    .filter(|x| !user.group_tests.iter().any(|y| y.pattern == x.pattern))
+                    .map(|x| (x, Layer::System)),
+            )
+            .map(|(test, layer)| {
+                sources.push(ValueSource {
+                    key: format!("group_tests.{}", test.pattern),
+                    value: serde_json::to_value(test).unwrap_or_default(),
+                    layer,
+                });
+                test.to_owned()
+            })
+            .collect();
+
+        // Presets of the user replace ones of the system with the same name
+        let mut presets = BTreeMap::new();
+        for (presets_of_layer, layer) in [
+            (&system.presets, Layer::System),
+            (&user.presets, Layer::User),
+        ] {
+            for (name, preset) in presets_of_layer {
+                presets.insert(name.to_owned(), (preset, layer));
+            }
+        }
+        let presets = presets
+            .into_iter()
+            .map(|(name, (preset, layer))| {
+                sources.push(ValueSource {
+                    key: format!("presets.{}", name),
+                    value: serde_json::to_value(preset).unwrap_or_default(),
+                    layer,
+                });
+                (name, preset.to_owned())
+            })
+            .collect();
+
+        let config = ConfigData {
+            servers,
+            using: layer_field("using", &system.using, &user.using, &mut sources),
+            tui: TuiConfig {
+                log_file: layer_field(
+                    "tui.log_file",
+                    &system.tui.log_file,
+                    &user.tui.log_file,
+                    &mut sources,
+                ),
+            },
+            sort: SortsConfig {
+                connections: layer_field(
+                    "sort.connections",
+                    &system.sort.connections,
+                    &user.sort.connections,
+                    &mut sources,
+                ),
+                rules: layer_field(
+                    "sort.rules",
+                    &system.sort.rules,
+                    &user.sort.rules,
+                    &mut sources,
+                ),
+                proxies: layer_field(
+                    "sort.proxies",
+                    &system.sort.proxies,
+                    &user.sort.proxies,
+                    &mut sources,
+                ),
+            },
+            profile: ProfileConfig {
+                path: layer_field(
+                    "profile.path",
+                    &system.profile.path,
+                    &user.profile.path,
+                    &mut sources,
+                ),
+                reload: layer_field(
+                    "profile.reload",
+                    &system.profile.reload,
+                    &user.profile.reload,
+                    &mut sources,
+                ),
+            },
+            group_tests,
+            presets,
+        };
+        (config, sources)
+    }
+
+    /// Part of the layered config that belongs to the user, to be written
+    /// as the layer over `system`: settings that `user` has set, or that
+    /// differ from `system`, and servers, tests and presets not in `system`
+    pub fn user_layer(&self, system: &ConfigData, user: &ConfigLayer) -> ConfigLayer {
+        fn setting<T: PartialEq + Clone>(value: &T, system: &T, user: &Option<T>) -> Option<T> {
+            (user.is_some() || value != system).then(|| value.to_owned())
+        }
+
+        ConfigLayer {
+            version: CONFIG_VERSION,
+            servers: self
+                .servers
+                .iter()
+                .filter(|x| !system.servers.contains(x))
+                .cloned()
+                .collect(),
+            using: setting(&self.using, &system.using, &user.using),
+            tui: TuiLayer {
+                log_file: setting(&self.tui.log_file, &system.tui.log_file, &user.tui.log_file),
+            },
+            sort: SortsLayer {
+                connections: setting(
+                    &self.sort.connections,
+                    &system.sort.connections,
+                    &user.sort.connections,
+                ),
+                rules: setting(&self.sort.rules, &system.sort.rules, &user.sort.rules),
+                proxies: setting(&self.sort.proxies, &system.sort.proxies, &user.sort.proxies),
+            },
+            profile: ProfileLayer {
+                path: setting(&self.profile.path, &system.profile.path, &user.profile.path),
+                reload: setting(
+                    &self.profile.reload,
+                    &system.profile.reload,
+                    &user.profile.reload,
+                ),
+            },
+            group_tests: self
+                .group_tests
+                .iter()
+                .filter(|x| !system.group_tests.contains(x))
+                .cloned()
+                .collect(),
+            presets: self
+                .presets
+                .iter()
+                .filter(|(name, preset)| system.presets.get(*name) != Some(preset))
+                .map(|(name, preset)| (name.to_owned(), preset.to_owned()))
+                .collect(),
+        }
+    }
+
+    /// Latency test of `group`, from the first of `group_tests` whose
+    /// pattern matches it
+    pub fn group_test(&self, group: &str) -> Option<&GroupTestConfig> {
+        self.group_tests.iter().find(|x| x.is_match(group))
+    }
+
+    /// Merge `servers` into configured ones, by name or URL. Servers that
+    /// differ from existing ones are only replaced with `overwrite`. Secrets
+    /// and names missing from imported servers are kept.
+    pub fn import_servers(&mut self, servers: Vec<Server>, overwrite: bool) -> Vec<ImportEntry> {
+        servers
+            .into_iter()
+            .map(|mut server| {
+                let mut entry = ImportEntry {
+                    name: server.name.clone(),
+                    url: server.url.clone(),
+                    action: ImportAction::Added,
+                    detail: None,
+                };
+                let by_name = server
+                    .name
+                    .as_ref()
+                    .and_then(|name| self.server_index(name).ok())
+                    .filter(|&index| self.servers[index].name == server.name);
+                let by_url = self.servers.iter().position(|x| x.url == server.url);
+
+                let index = match (by_name, by_url) {
+                    (Some(a), Some(b)) if a != b => {
+                        entry.action = ImportAction::Conflict;
+                        entry.detail = Some(format!(
+                            "name is taken by {}, and URL by {}",
+                            self.servers[a], self.servers[b]
+                        ));
+                        return entry;
+                    }
+                    (None, None) => {
+                        if let Err(e) = self.add_server(server) {
+                            entry.action = ImportAction::Invalid;
+                            entry.detail = Some(e.to_string());
+                        }
+                        return entry;
+                    }
+                    (Some(index), _) | (None, Some(index)) => index,
+                };
+
+                let existing = &self.servers[index];
+                let no_secret = server.secret.is_none()
+                    && server.secret_env.is_none()
+                    && server.secret_file.is_none()
+                    && server.secret_cmd.is_none();
+                if no_secret {
+                    server.secret = existing.secret.clone();
+                    server.secret_env = existing.secret_env.clone();
+                    server.secret_file = existing.secret_file.clone();
+                    server.secret_cmd = existing.secret_cmd.clone();
+                }
+                if server.name.is_none() {
+                    server.name = existing.name.clone();
+                }
+                entry.name = server.name.clone();
+
+                let diff = server_diff(existing, &server);
+                if diff.is_empty() {
+                    entry.action = ImportAction::Unchanged;
+                    return entry;
+                }
+                entry.detail = Some(format!("different {}", diff.join(", ")));
+                if !overwrite {
+                    entry.action = ImportAction::Conflict;
+                } else if let Some(Err(e)) = server
+                    .name
+                    .as_ref()
+                    .filter(|_| diff.contains(&"name"))
+                    .map(|name| self.check_server_name(name))
+                {
+                    entry.action = ImportAction::Invalid;
+                    entry.detail = Some(e.to_string());
+                } else {
+                    entry.action = ImportAction::Updated;
+                    self.servers[index] = server;
+                }
+                entry
+            })
+            .collect()
+    }
+
+    /// Find a server by its name, or by its URL
+    pub fn find_server(&self, query: &str) -> InteractiveResult<&Server> {
+        self.server_index(query).map(|index| &self.servers[index])
+    }
+
+    /// Add a new server. Both its name and URL must not be taken.
+    pub fn add_server(&mut self, server: Server) -> InteractiveResult<()> {
+        if let Some(ref name) = server.name {
+            self.check_server_name(name)?;
+        }
+        if self.servers.iter().any(|x| x.url == server.url) {
+            return Err(InteractiveError::ServerExists(server.url.to_string()));
+        }
+        self.servers.push(server);
+        Ok(())
+    }
+
+    pub fn rename_server(&mut self, query: &str, name: &str) -> InteractiveResult<()> {
+        let index = self.server_index(query)?;
+        if self.servers[index].name.as_deref() != Some(name) {
+            self.check_server_name(name)?;
+        }
+        self.servers[index].name = Some(name.to_owned());
+        Ok(())
+    }
+
+    /// Remove a server by its name or URL. Active server is unset if it's
+    /// the one removed.
+    pub fn remove_server(&mut self, query: &str) -> InteractiveResult<Server> {
+        let index = self.server_index(query)?;
+        let server = self.servers.remove(index);
+        if self.using.as_ref() == Some(&server.url) {
+            self.using = None;
+        }
+        Ok(server)
+    }
+
+    fn server_index(&self, query: &str) -> InteractiveResult<usize> {
+        self.servers
+            .iter()
+            .position(|x| x.name.as_deref() == Some(query))
+            .or_else(|| {
+                let url = Url::parse(query).ok()?;
+                self.servers.iter().position(|x| x.url == url)
+            })
+            .ok_or_else(|| InteractiveError::ServerNameNotFound(query.to_owned()))
+    }
+
+    fn check_server_name(&self, name: &str) -> InteractiveResult<()> {
+        // Names that parse as URLs would be ambiguous with URLs of servers
+        if name.trim().is_empty() || Url::parse(name).is_ok() {
+            return Err(InteractiveError::InvalidServerName(name.to_owned()));
+        }
+        if self.servers.iter().any(|x| x.name.as_deref() == Some(name)) {
+            return Err(InteractiveError::ServerExists(name.to_owned()));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
 pub struct TuiConfig {
     pub log_file: Option<PathBuf>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+/// Latency test of groups whose names match `pattern`, e.g. streaming
+/// groups tested against the site they unlock
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct GroupTestConfig {
+    /// Regex of group names, e.g. `(?i)netflix|disney`
+    pub pattern: GroupPattern,
+    /// URL to test members against, in place of the default of `--test-url`
+    #[serde(default)]
+    pub url: Option<Url>,
+    /// Timeout of each test in ms, in place of the default of `--timeout`
+    #[serde(default)]
+    pub timeout: Option<u64>,
+}
+
+impl GroupTestConfig {
+    pub fn is_match(&self, group: &str) -> bool {
+        self.pattern.is_match(group)
+    }
+}
+
+/// Regex of group names in [`GroupTestConfig`], compiled once when the config
+/// is loaded. Invalid patterns match nothing.
+#[derive(Debug, Clone)]
+pub struct GroupPattern {
+    pattern: String,
+    regex: Result<Regex, regex::Error>,
+}
+
+impl GroupPattern {
+    pub fn new(pattern: &str) -> Self {
+        Self {
+            pattern: pattern.to_owned(),
+            regex: Regex::new(pattern),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Why the pattern is not a valid regex, if it's not
+    pub fn error(&self) -> Option<&regex::Error> {
+        self.regex.as_ref().err()
+    }
+
+    pub fn is_match(&self, group: &str) -> bool {
+        self.regex.as_ref().is_ok_and(|x| x.is_match(group))
+    }
+}
+
+impl PartialEq for GroupPattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.pattern == other.pattern
+    }
+}
+
+impl Eq for GroupPattern {}
+
+impl Display for GroupPattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.pattern)
+    }
+}
+
+impl Serialize for GroupPattern {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.pattern)
+    }
+}
+
+impl<'de> Deserialize<'de> for GroupPattern {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(|x| Self::new(&x))
+    }
+}
+
+/// Intervals of refreshing data in TUI, in ms
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, SmartDefault,
+)]
+#[serde(default)]
+pub struct RefreshIntervals {
+    /// Also the default interval of `conn watch`
+    #[default(1000)]
+    pub connections: u64,
+    #[default(5000)]
+    pub proxies: u64,
+    #[default(5000)]
+    pub rules: u64,
+    /// Version and configs of Clash
+    #[default(5000)]
+    pub configs: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct ProfileConfig {
+    /// Local Clash profile that generated rules are inserted into
+    #[serde(default)]
+    pub path: Option<PathBuf>,
+    /// Ask Clash to reload the profile after it's modified
+    #[serde(default)]
+    pub reload: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
 pub struct SortsConfig {
     pub connections: ConSort,
     pub rules: RuleSort,
     pub proxies: ProxySort,
 }
+
+/// [`TuiConfig`] of a [`ConfigLayer`]
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct TuiLayer {
+    #[serde(default, with = "present", skip_serializing_if = "Option::is_none")]
+    pub log_file: Option<Option<PathBuf>>,
+}
+
+impl TuiLayer {
+    fn is_unset(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// [`ProfileConfig`] of a [`ConfigLayer`]
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct ProfileLayer {
+    #[serde(default, with = "present", skip_serializing_if = "Option::is_none")]
+    pub path: Option<Option<PathBuf>>,
+    #[serde(default, with = "present", skip_serializing_if = "Option::is_none")]
+    pub reload: Option<bool>,
+}
+
+impl ProfileLayer {
+    fn is_unset(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// [`SortsConfig`] of a [`ConfigLayer`]
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct SortsLayer {
+    #[serde(default, with = "present", skip_serializing_if = "Option::is_none")]
+    pub connections: Option<ConSort>,
+    #[serde(default, with = "present", skip_serializing_if = "Option::is_none")]
+    pub rules: Option<RuleSort>,
+    #[serde(default, with = "present", skip_serializing_if = "Option::is_none")]
+    pub proxies: Option<ProxySort>,
+}
+
+impl SortsLayer {
+    fn is_unset(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+#[test]
+fn test_server_names() {
+    let server = |url, name| Server::test(url, name, None);
+    let mut config = ConfigData::default();
+    config
+        .add_server(server("http://127.0.0.1:9090", Some("local")))
+        .unwrap();
+    config
+        .add_server(server("http://10.0.0.1:9090", None))
+        .unwrap();
+    config.using = Some(Url::parse("http://10.0.0.1:9090").unwrap());
+
+    assert!(config
+        .add_server(server("http://127.0.0.1:9090", Some("other")))
+        .is_err());
+    assert!(config
+        .add_server(server("http://10.0.0.2:9090", Some("local")))
+        .is_err());
+    assert!(config
+        .add_server(server("http://10.0.0.2:9090", Some("http://x")))
+        .is_err());
+
+    assert_eq!(
+        config.find_server("local").unwrap().url.as_str(),
+        "http://127.0.0.1:9090/"
+    );
+    assert!(config.find_server("http://127.0.0.1:9090").is_ok());
+    assert!(config.find_server("remote").is_err());
+
+    config.rename_server("http://10.0.0.1:9090", "gw").unwrap();
+    assert!(config.rename_server("gw", "local").is_err());
+    config.rename_server("local", "local").unwrap();
+
+    config.remove_server("gw").unwrap();
+    assert_eq!(config.servers.len(), 1);
+    assert!(config.using.is_none());
+}
+
+#[test]
+fn test_config_migrate() {
+    let mut config = ConfigLayer {
+        version: 0,
+        ..Default::default()
+    };
+    config.using = Some(Some(Url::parse("http://127.0.0.1:9090").unwrap()));
+    let config = config.migrate().unwrap();
+    assert_eq!(config.version, CONFIG_VERSION);
+    assert!(config.using.is_none());
+
+    assert_eq!(ConfigLayer::default().version, CONFIG_VERSION);
+    let newer = ConfigLayer {
+        version: CONFIG_VERSION + 1,
+        ..Default::default()
+    };
+    assert!(newer.migrate().is_err());
+}
+
+#[test]
+fn test_config_migrate_v0() {
+    // Written by builds before config versions, with every setting
+    let raw = r#"(
+  servers: [
+    (url: "http://127.0.0.1:9090", secret: None),
+  ],
+  using: Some("http://127.0.0.1:9090"),
+  tui: (log_file: None),
+  sort: (
+    connections: (by: time, order: descendant),
+    rules: (by: proxy, order: ascendant),
+    proxies: (by: delay, order: ascendant),
+  ),
+)"#;
+    let config = ron::from_str::<ConfigLayer>(raw)
+        .unwrap()
+        .migrate()
+        .unwrap();
+    assert_eq!(config.version, CONFIG_VERSION);
+    assert_eq!(config.servers.len(), 1);
+    assert_eq!(config.using, Some(Some(config.servers[0].url.clone())));
+    assert_eq!(config.tui, TuiLayer::default());
+    assert_eq!(config.sort.connections, None);
+    assert_eq!(config.sort.rules, Some(RuleSort::by_proxy_name_asc()));
+    assert_eq!(config.sort.proxies, None);
+
+    let raw = raw.replace(r#"Some("http://127.0.0.1:9090")"#, "None");
+    let config = ron::from_str::<ConfigLayer>(&raw)
+        .unwrap()
+        .migrate()
+        .unwrap();
+    assert_eq!(config.using, None);
+}
+
+#[test]
+fn test_config_layered() {
+    let server = |url, name| Server::test(url, Some(name), None);
+    let system = ConfigLayer {
+        servers: vec![
+            server("http://10.0.0.1:9090", "gw"),
+            server("http://10.0.0.2:9090", "hk"),
+        ],
+        using: Some(Some(Url::parse("http://10.0.0.1:9090").unwrap())),
+        profile: ProfileLayer {
+            path: Some(Some("/etc/clash/config.yaml".into())),
+            reload: Some(true),
+        },
+        ..Default::default()
+    };
+    let user = ConfigLayer {
+        servers: vec![
+            server("http://10.0.0.2:9090", "hongkong"),
+            server("http://127.0.0.1:9090", "local"),
+        ],
+        tui: TuiLayer {
+            log_file: Some(Some("/tmp/clashctl.log".into())),
+        },
+        // Set to the default, which still overrides the system
+        profile: ProfileLayer {
+            path: None,
+            reload: Some(false),
+        },
+        ..Default::default()
+    };
+
+    let (layered, sources) = ConfigData::layered(&system, &user);
+    let names = layered
+        .servers
+        .iter()
+        .map(|x| x.name.as_deref().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["gw", "hongkong", "local"]);
+    assert_eq!(Some(layered.using.clone()), system.using);
+    assert!(!layered.profile.reload);
+    assert_eq!(Some(layered.profile.path.clone()), system.profile.path);
+    assert_eq!(Some(layered.tui.log_file.clone()), user.tui.log_file);
+
+    let layer_of = |key: &str| sources.iter().find(|x| x.key == key).unwrap().layer;
+    assert_eq!(layer_of("servers.gw"), Layer::System);
+    assert_eq!(layer_of("servers.local"), Layer::User);
+    assert_eq!(layer_of("using"), Layer::System);
+    assert_eq!(layer_of("tui.log_file"), Layer::User);
+    assert_eq!(layer_of("sort.rules"), Layer::Default);
+
+    assert_eq!(layer_of("profile.reload"), Layer::User);
+    assert_eq!(layer_of("profile.path"), Layer::System);
+
+    // Settings the user has set are kept even if they equal the system ones,
+    // and ones changed from the system are set
+    let mut changed = layered.clone();
+    changed.using = None;
+    let stripped = changed.user_layer(&system.resolve(), &user);
+    assert_eq!(stripped.servers.len(), 2);
+    assert_eq!(stripped.using, Some(None));
+    assert_eq!(stripped.profile.reload, Some(false));
+    assert_eq!(stripped.profile.path, None);
+    assert_eq!(stripped.tui.log_file, user.tui.log_file);
+    assert_eq!(
+        ConfigData::layered(&system, &stripped).0.using,
+        None,
+        "Unset in the user layer hides the system one"
+    );
+}
+
+#[test]
+fn test_import_servers() {
+    let server = Server::test;
+    let mut config = ConfigData {
+        servers: vec![
+            server("http://10.0.0.1:9090", Some("gw"), Some("s3cret")),
+            server("http://10.0.0.2:9090", Some("hk"), None),
+        ],
+        ..Default::default()
+    };
+
+    let imported = vec![
+        // Exported without secret
+        server("http://10.0.0.1:9090", Some("gw"), None),
+        server("http://10.0.0.2:9090", Some("hk"), Some("new")),
+        server("http://10.0.0.3:9090", Some("jp"), None),
+        server("http://10.0.0.1:9090", Some("hk"), None),
+        server("http://10.0.0.4:9090", Some("http://x"), None),
+    ];
+    let actions =
+        |entries: Vec<ImportEntry>| entries.into_iter().map(|x| x.action).collect::<Vec<_>>();
+
+    let mut dry = config.clone();
+    assert_eq!(
+        actions(dry.import_servers(imported.clone(), false)),
+        [
+            ImportAction::Unchanged,
+            ImportAction::Conflict,
+            ImportAction::Added,
+            ImportAction::Conflict,
+            ImportAction::Invalid
+        ]
+    );
+
+    assert_eq!(
+        actions(config.import_servers(imported, true))[1],
+        ImportAction::Updated
+    );
+    assert_eq!(config.servers.len(), 3);
+    assert_eq!(config.servers[0].secret.as_deref(), Some("s3cret"));
+    assert_eq!(config.servers[1].secret.as_deref(), Some("new"));
+}
+
+#[test]
+fn test_group_tests() {
+    let test = |pattern: &str, url: &str| GroupTestConfig {
+        pattern: GroupPattern::new(pattern),
+        url: Some(Url::parse(url).unwrap()),
+        timeout: None,
+    };
+    let system = ConfigLayer {
+        group_tests: vec![
+            test("(?i)netflix", "https://www.netflix.com/title/80018499"),
+            test("^Disney", "https://www.disneyplus.com"),
+        ],
+        ..Default::default()
+    };
+    let user = ConfigLayer {
+        group_tests: vec![
+            test("(?i)netflix|nf", "https://www.netflix.com/title/70143836"),
+            test("[", "http://invalid"),
+        ],
+        ..Default::default()
+    };
+
+    let (layered, sources) = ConfigData::layered(&system, &user);
+    let url_of = |group| {
+        layered
+            .group_test(group)
+            .and_then(|x| x.url.as_ref())
+            .map(Url::as_str)
+    };
+    assert_eq!(url_of("NF"), Some("https://www.netflix.com/title/70143836"));
+    assert_eq!(
+        url_of("Netflix"),
+        Some("https://www.netflix.com/title/70143836")
+    );
+    assert_eq!(url_of("Disney+"), Some("https://www.disneyplus.com/"));
+    assert_eq!(url_of("Proxy"), None);
+    assert!(user.group_tests[1].pattern.error().is_some());
+    assert_eq!(
+        sources
+            .iter()
+            .find(|x| x.key == "group_tests.^Disney")
+            .unwrap()
+            .layer,
+        Layer::System
+    );
+
+    assert_eq!(
+        layered.user_layer(&system.resolve(), &user).group_tests,
+        user.group_tests
+    );
+}
@@ -8,6 +8,30 @@ pub enum InteractiveError {
     #[error("Cannot find server")]
     ServerNotFound,
 
+    #[error("Cannot find server `{0}`")]
+    ServerNameNotFound(String),
+
+    #[error("Invalid server URL `{0}` ({1})")]
+    InvalidServerUrl(String, url::ParseError),
+
+    #[error("Cannot get secret of {0} ({1})")]
+    SecretError(String, String),
+
+    #[error("Server `{0}` comes from system config, and cannot be removed")]
+    SystemServer(String),
+
+    #[error("Preset `{0}` comes from system config, and cannot be removed")]
+    SystemPreset(String),
+
+    #[error("{0} is read-only, set `read_only: false` in config to change it")]
+    ReadOnlyServer(String),
+
+    #[error("Server `{0}` already exists")]
+    ServerExists(String),
+
+    #[error("Invalid server name `{0}`, it cannot be empty or a URL")]
+    InvalidServerName(String),
+
     #[error("{0} is not a directory")]
     ConfigFileTypeError(PathBuf),
 
@@ -22,6 +46,24 @@ pub enum InteractiveError {
 
     #[error("Config file cannot be generated ({0})")]
     ConfigFileGenerateError(#[from] ron::Error),
+
+    #[error("Config file cannot be parsed ({0})")]
+    ConfigFileTomlError(#[from] toml::de::Error),
+
+    #[error("Config file cannot be generated ({0})")]
+    ConfigFileTomlGenerateError(#[from] toml::ser::Error),
+
+    #[error(
+        "Config file is of version {0}, which is newer than this clashctl supports. Please \
+         upgrade clashctl"
+    )]
+    ConfigVersionError(u32),
+
+    #[error("Profile path is not set, use `--profile` or set `profile.path` in config")]
+    ProfileNotSet,
+
+    #[error("Profile IO error ({0})")]
+    ProfileIoError(std::io::Error),
 }
 
 pub type InteractiveResult<T> = std::result::Result<T, InteractiveError>;
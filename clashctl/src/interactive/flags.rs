@@ -1,84 +1,263 @@
-use std::{path::PathBuf, time::Duration};
+use std::{
+    env,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
 use clap::Parser;
-use clashctl_core::Clash;
+use clashctl_core::{strum::VariantNames, Clash};
 use home::home_dir;
-use log::debug;
+use log::{debug, warn};
 use url::Url;
 
-use super::{Config, InteractiveError, InteractiveResult};
+use super::{Config, ConfigData, GroupTestConfig, InteractiveError, InteractiveResult, Server};
+use crate::OutputFormat;
 
 const DEFAULT_TEST_URL: &str = "http://www.gstatic.com/generate_204";
 
+const DEFAULT_TIMEOUT: u64 = 2000;
+
+/// URL of the server to use when `--server` is not given
+pub const URL_ENV: &str = "CLASHCTL_URL";
+
+/// Directory of config shared by all users, managed by admins
+pub const SYSTEM_CONFIG_DIR: &str = "/etc/clashctl";
+
+/// Path of the system config, in place of the one in `/etc/clashctl`
+pub const SYSTEM_CONFIG_ENV: &str = "CLASHCTL_SYSTEM_CONFIG";
+
+/// Secret of servers given by `CLASHCTL_URL` or by URLs not in config
+pub const SECRET_ENV: &str = "CLASHCTL_SECRET";
+
 #[derive(Clone, Debug, Parser)]
 pub struct Flags {
     #[clap(short, long, parse(from_occurrences))]
     /// Verbosity. Default: INFO, -v DEBUG, -vv TRACE
     pub verbose: u8,
 
-    #[clap(short, long, default_value = "2000")]
-    /// Timeout of requests, in ms
-    pub timeout: u64,
+    #[clap(short, long)]
+    /// Timeout of requests, in ms. Default to `timeout` of the server, or
+    /// 2000
+    pub timeout: Option<u64>,
 
-    #[clap(long, conflicts_with = "config-path")]
+    #[clap(long, conflicts_with = "config-path", value_parser)]
     /// Path of config directory. Default to ~/.config/clashctl
     pub config_dir: Option<PathBuf>,
 
-    #[clap(short, long, conflicts_with = "config-dir")]
-    /// Path of config file. Default to ~/.config/clashctl/config.ron
+    #[clap(short, long, conflicts_with = "config-dir", value_parser)]
+    /// Path of config file, in RON or TOML by its extension. Default to
+    /// ~/.config/clashctl/config.ron, or config.toml if it exists instead
     pub config_path: Option<PathBuf>,
 
+    #[clap(short, long, global = true)]
+    /// Name or URL of the server to use, instead of the active one. Default
+    /// to `CLASHCTL_URL` if set
+    pub server: Option<String>,
+
+    #[clap(long)]
+    /// Url for testing proxy endpointes. Default to `test_url` of the server,
+    /// or http://www.gstatic.com/generate_204
+    pub test_url: Option<Url>,
+
     #[clap(
-            long,
-            default_value = DEFAULT_TEST_URL,
-        )
-    ]
-    /// Url for testing proxy endpointes
-    pub test_url: Url,
+        short,
+        long,
+        default_value = "table",
+        possible_values = OutputFormat::VARIANTS,
+    )]
+    /// Output format of commands
+    pub output: OutputFormat,
+
+    #[clap(skip)]
+    /// Whether problems of the config have been warned about. Config is read
+    /// more than once by some commands, but they are warned about only once
+    pub(crate) warned: Arc<AtomicBool>,
 }
 
 impl Default for Flags {
     fn default() -> Self {
         Self {
             verbose: 0,
-            timeout: 2000,
+            timeout: None,
             config_dir: None,
             config_path: None,
-            test_url: Url::parse(DEFAULT_TEST_URL).unwrap(),
+            server: None,
+            test_url: None,
+            output: OutputFormat::Table,
+            warned: Default::default(),
         }
     }
 }
 
 impl Flags {
-    pub fn get_config(&self) -> InteractiveResult<Config> {
-        if let Some(ref dir) = self.config_path {
-            return Config::from_dir(dir);
+    /// Timeout of requests to `server` in ms, from `--timeout`, then the
+    /// override of the server
+    pub fn timeout(&self, server: Option<&Server>) -> u64 {
+        self.timeout
+            .or_else(|| server.and_then(|x| x.timeout))
+            .unwrap_or(DEFAULT_TIMEOUT)
+    }
+
+    /// URL to test latency of members of a group against, from
+    /// `--test-url`, then the group test matching the group, then the override
+    /// of the server
+    pub fn test_url(&self, server: Option<&Server>, group: Option<&GroupTestConfig>) -> Url {
+        self.test_url
+            .as_ref()
+            .or_else(|| group.and_then(|x| x.url.as_ref()))
+            .or_else(|| server.and_then(|x| x.test_url.as_ref()))
+            .cloned()
+            .unwrap_or_else(|| Url::parse(DEFAULT_TEST_URL).unwrap())
+    }
+
+    /// Timeout of each latency test in ms, from `--timeout`, then the group
+    /// test matching the group, then the override of the server
+    pub fn test_timeout(&self, server: Option<&Server>, group: Option<&GroupTestConfig>) -> u64 {
+        self.timeout
+            .or_else(|| group.and_then(|x| x.timeout))
+            .unwrap_or_else(|| self.timeout(server))
+    }
+
+    /// Longest timeout of latency tests with any of `group_tests`, for
+    /// requests to wait for them
+    pub fn max_test_timeout(
+        &self,
+        server: Option<&Server>,
+        group_tests: &[GroupTestConfig],
+    ) -> u64 {
+        group_tests
+            .iter()
+            .map(Some)
+            .chain([None])
+            .map(|group| self.test_timeout(server, group))
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// Path of the config file, from `--config-path` or `--config-dir`
+    pub fn config_path(&self) -> InteractiveResult<PathBuf> {
+        if let Some(ref path) = self.config_path {
+            return Ok(path.to_owned());
         }
-        let conf_dir = self
-            .config_dir
+        self.config_dir
             .to_owned()
             .or_else(|| home_dir().map(|dir| dir.join(".config/clashctl/")))
-            .ok_or(InteractiveError::ConfigFileOpenError)?;
+            .map(|dir| {
+                // Use TOML only if there's no RON
+                let toml = dir.join("config.toml");
+                if toml.exists() && !dir.join("config.ron").exists() {
+                    toml
+                } else {
+                    dir.join("config.ron")
+                }
+            })
+            .ok_or(InteractiveError::ConfigFileOpenError)
+    }
+
+    /// Path of the system config, from `CLASHCTL_SYSTEM_CONFIG` or in
+    /// `/etc/clashctl`. Empty `CLASHCTL_SYSTEM_CONFIG` disables it.
+    pub fn system_config_path(&self) -> Option<PathBuf> {
+        match env::var_os(SYSTEM_CONFIG_ENV) {
+            Some(path) if path.is_empty() => None,
+            Some(path) => Some(path.into()),
+            None => ["config.ron", "config.toml"]
+                .iter()
+                .map(|name| Path::new(SYSTEM_CONFIG_DIR).join(name))
+                .find(|path| path.exists()),
+        }
+    }
+
+    /// User config, layered over the system config if there is one
+    pub fn get_config(&self) -> InteractiveResult<Config> {
+        let config = self.get_user_config()?;
+        let config = match self.system_config_path() {
+            Some(path) if path.exists() => config.with_system(path)?,
+            _ => config,
+        };
+        if !self.warned.swap(true, Ordering::Relaxed) {
+            for test in &config.group_tests {
+                if test.pattern.error().is_some() {
+                    warn!(
+                        "Ignoring group test `{}`, as it's not a valid regex",
+                        test.pattern
+                    );
+                }
+            }
+        }
+        Ok(config)
+    }
+
+    /// Config for optional settings like `group_tests`, or the default config
+    /// if it can't be read, e.g. in containers with only `CLASHCTL_URL` set
+    pub fn get_config_or_default(&self) -> ConfigData {
+        match self.get_config() {
+            Ok(config) => config.get_inner().clone(),
+            Err(e) => {
+                debug!("Using default config, as config can't be read ({})", e);
+                ConfigData::default()
+            }
+        }
+    }
+
+    fn get_user_config(&self) -> InteractiveResult<Config> {
+        let path = self.config_path()?;
+        if self.config_path.is_some() {
+            return Config::from_dir(path);
+        }
+        let conf_dir = path.parent().expect("Config file should be in a directory");
 
         if !conf_dir.exists() {
             debug!("Config directory does not exist, creating.");
-            std::fs::create_dir_all(&conf_dir).map_err(InteractiveError::ConfigFileIoError)?;
+            std::fs::create_dir_all(conf_dir).map_err(InteractiveError::ConfigFileIoError)?;
         }
 
         if !conf_dir.is_dir() {
-            Err(InteractiveError::ConfigFileTypeError(conf_dir))
+            Err(InteractiveError::ConfigFileTypeError(conf_dir.to_owned()))
         } else {
             debug!("Path to config: {}", conf_dir.display());
-            Config::from_dir(conf_dir.join("config.ron"))
+            Config::from_dir(path)
         }
     }
 
-    pub fn connect_server_from_config(&self) -> InteractiveResult<Clash> {
-        let config = self.get_config()?;
-        let server = config
-            .using_server()
-            .ok_or(InteractiveError::ServerNotFound)?
-            .to_owned();
-        server.into_clash_with_timeout(Some(Duration::from_millis(self.timeout)))
+    /// Server to connect to, in the order of:
+    ///
+    /// - `--server`, by name or URL of a configured server. Other URLs are used
+    ///   as is, with secret from `CLASHCTL_SECRET`
+    /// - `CLASHCTL_URL` and `CLASHCTL_SECRET`, without reading config file
+    /// - Active server in config
+    pub fn get_server(&self) -> InteractiveResult<Option<Server>> {
+        let from_env = |url: Url| Server::new(url, env::var(SECRET_ENV).ok());
+
+        if let Some(ref query) = self.server {
+            // URLs work without config, so errors of reading it only matter
+            // for names
+            let found = self
+                .get_config()
+                .and_then(|config| config.find_server(query).cloned());
+            return match (found, Url::parse(query)) {
+                (Ok(server), _) => Ok(Some(server)),
+                (Err(_), Ok(url)) => Ok(Some(from_env(url))),
+                (Err(e), Err(_)) => Err(e),
+            };
+        }
+
+        if let Ok(url) = env::var(URL_ENV) {
+            let url = Url::parse(&url).map_err(|e| InteractiveError::InvalidServerUrl(url, e))?;
+            debug!("Using server from `{}`", URL_ENV);
+            return Ok(Some(from_env(url)));
+        }
+
+        Ok(self.get_config()?.using_server().cloned())
+    }
+
+    /// Connect to `server` with its timeout
+    pub fn connect(&self, server: &Server) -> InteractiveResult<Clash> {
+        server
+            .clone()
+            .into_clash_with_timeout(Some(Duration::from_millis(self.timeout(Some(server)))))
     }
 }
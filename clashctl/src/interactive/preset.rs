@@ -0,0 +1,116 @@
+use std::collections::BTreeMap;
+
+use clashctl_core::{model::Proxies, Clash};
+use log::debug;
+use serde::Serialize;
+
+use super::InteractiveResult;
+
+/// Selected member of each selector group, by group name
+pub type Preset = BTreeMap<String, String>;
+
+/// What applying a preset did to a group
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PresetAction {
+    Applied,
+    Unchanged,
+    /// The group no longer exists, or is not a selector
+    MissingGroup,
+    /// The saved member is no longer in the group
+    MissingMember,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct PresetEntry {
+    pub group: String,
+    pub proxy: String,
+    pub action: PresetAction,
+}
+
+/// Current selection of every selector group
+pub fn snapshot_preset(proxies: &Proxies) -> Preset {
+    proxies
+        .selectors()
+        .filter_map(|(name, proxy)| Some((name.to_owned(), proxy.now.to_owned()?)))
+        .collect()
+}
+
+/// What applying `preset` would do, without changing anything. Groups to be
+/// changed are marked as [`PresetAction::Applied`].
+pub fn plan_preset(proxies: &Proxies, preset: &Preset) -> Vec<PresetEntry> {
+    preset
+        .iter()
+        .map(|(group, proxy)| {
+            let action = match proxies.get(group) {
+                Some(x) if !x.proxy_type.is_selector() => PresetAction::MissingGroup,
+                None => PresetAction::MissingGroup,
+                Some(x) if x.now.as_ref() == Some(proxy) => PresetAction::Unchanged,
+                Some(x) if !x.all.iter().flatten().any(|member| member == proxy) => {
+                    PresetAction::MissingMember
+                }
+                Some(_) => PresetAction::Applied,
+            };
+            PresetEntry {
+                group: group.to_owned(),
+                proxy: proxy.to_owned(),
+                action,
+            }
+        })
+        .collect()
+}
+
+/// Select members saved in `preset`. Groups and members that no longer exist
+/// are skipped, and reported in the returned entries. Either all the other
+/// groups are changed, or none of them is.
+pub fn apply_preset(clash: &Clash, preset: &Preset) -> InteractiveResult<Vec<PresetEntry>> {
+    let entries = plan_preset(&clash.get_proxies()?, preset);
+    let selections = entries
+        .iter()
+        .filter(|x| x.action == PresetAction::Applied)
+        .map(|x| (&x.group, &x.proxy))
+        .collect::<Vec<_>>();
+    debug!("Selecting {:?}", selections);
+    clash.apply_selections(&selections)?;
+    Ok(entries)
+}
+
+#[test]
+fn test_plan_preset() {
+    let proxies: Proxies = serde_json::from_str(
+        r#"{"proxies": {
+            "Proxy": {"type": "Selector", "name": "Proxy", "now": "HK-1", "all": ["HK-1", "JP-1"], "history": [], "udp": false},
+            "Auto": {"type": "URLTest", "name": "Auto", "now": "HK-1", "all": ["HK-1"], "history": [], "udp": false},
+            "Streaming": {"type": "Selector", "name": "Streaming", "now": "JP-1", "all": ["JP-1", "US-1"], "history": [], "udp": false},
+            "HK-1": {"type": "Shadowsocks", "name": "HK-1", "history": [], "udp": true},
+            "JP-1": {"type": "Shadowsocks", "name": "JP-1", "history": [], "udp": true}
+        }}"#,
+    )
+    .unwrap();
+
+    let snapshot = snapshot_preset(&proxies);
+    assert_eq!(snapshot.len(), 2);
+    assert_eq!(snapshot["Proxy"], "HK-1");
+
+    let preset = [
+        ("Proxy", "JP-1"),
+        ("Streaming", "SG-1"),
+        ("Auto", "HK-1"),
+        ("Gone", "HK-1"),
+    ]
+    .into_iter()
+    .map(|(group, proxy)| (group.to_owned(), proxy.to_owned()))
+    .collect::<Preset>();
+    let actions = plan_preset(&proxies, &preset)
+        .into_iter()
+        .map(|x| (x.group, x.action))
+        .collect::<BTreeMap<_, _>>();
+    assert_eq!(actions["Proxy"], PresetAction::Applied);
+    assert_eq!(
+        plan_preset(&proxies, &snapshot)[0].action,
+        PresetAction::Unchanged
+    );
+    assert_eq!(actions["Streaming"], PresetAction::MissingMember);
+    assert_eq!(actions["Auto"], PresetAction::MissingGroup);
+    assert_eq!(actions["Gone"], PresetAction::MissingGroup);
+}
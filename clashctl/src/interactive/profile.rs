@@ -0,0 +1,60 @@
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::Path,
+};
+
+use clashctl_core::{Clash, Profile, RulePosition};
+use log::{debug, info};
+
+use super::{sibling, InteractiveError, InteractiveResult};
+
+/// Insert `rule` into the local profile at `path`. When `reload` is given,
+/// Clash will be asked to reload the modified profile afterwards.
+///
+/// Returns the index of the inserted rule among all rules in the profile.
+pub fn insert_rule_into_profile(
+    path: &Path,
+    rule: &str,
+    position: RulePosition,
+    reload: Option<&Clash>,
+) -> InteractiveResult<usize> {
+    debug!("Inserting `{}` into profile @ {}", rule, path.display());
+
+    let raw = fs::read_to_string(path).map_err(InteractiveError::ProfileIoError)?;
+    let mut profile = Profile::parse(&raw);
+    let index = profile.insert_rule(rule, position)?;
+    write_profile(path, &profile.to_string()).map_err(InteractiveError::ProfileIoError)?;
+
+    info!(
+        "Inserted `{}` as rule #{} of {}",
+        rule,
+        index,
+        path.display()
+    );
+
+    if let Some(clash) = reload {
+        // Clash requires an absolute path
+        let path = fs::canonicalize(path).map_err(InteractiveError::ProfileIoError)?;
+        clash.reload_configs(false, &path.to_string_lossy())?;
+        info!("Profile reloaded");
+    }
+
+    Ok(index)
+}
+
+/// Replace the profile through a temp file next to it, so that Clash never
+/// sees a partly written one. Symlinks are followed, and permissions of the
+/// profile are kept, as it may hold the secret.
+fn write_profile(path: &Path, content: &str) -> io::Result<()> {
+    let path = fs::canonicalize(path)?;
+    let temp = sibling(&path, "tmp");
+    let mut file = File::create(&temp)?;
+    file.set_permissions(fs::metadata(&path)?.permissions())
+        .and_then(|_| file.write_all(content.as_bytes()))
+        .and_then(|_| file.sync_all())
+        .and_then(|_| fs::rename(&temp, &path))
+        .inspect_err(|_| {
+            let _ = fs::remove_file(&temp);
+        })
+}
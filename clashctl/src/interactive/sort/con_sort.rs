@@ -1,12 +1,16 @@
+use std::{cmp::Ordering, fmt::Display};
+
 use clashctl_core::model::ConnectionWithSpeed;
 use serde::{Deserialize, Serialize};
 use smart_default::SmartDefault;
+use strum::IntoEnumIterator;
 
 use crate::{EndlessSelf, OrderBy, SortMethod, SortOrder};
 
 #[derive(
     Debug,
     Clone,
+    Copy,
     PartialEq,
     Eq,
     PartialOrd,
@@ -15,9 +19,13 @@ use crate::{EndlessSelf, OrderBy, SortMethod, SortOrder};
     Deserialize,
     SmartDefault,
     strum::EnumIter,
+    strum::EnumString,
+    strum::Display,
+    strum::EnumVariantNames,
 )]
 #[serde(rename_all = "lowercase")]
-enum ConSortBy {
+#[strum(ascii_case_insensitive, serialize_all = "lowercase")]
+pub enum ConSortBy {
     Host,
     Down,
     Up,
@@ -33,18 +41,46 @@ enum ConSortBy {
 }
 
 impl SortMethod<ConnectionWithSpeed> for ConSortBy {
-    fn sort_fn(&self, _a: &ConnectionWithSpeed, _b: &ConnectionWithSpeed) -> std::cmp::Ordering {
-        todo!()
+    fn sort_fn(&self, a: &ConnectionWithSpeed, b: &ConnectionWithSpeed) -> Ordering {
+        let (a_meta, b_meta) = (&a.connection.metadata, &b.connection.metadata);
+        match self {
+            Self::Host => a_meta.host.cmp(&b_meta.host),
+            Self::Down => a.connection.download.cmp(&b.connection.download),
+            Self::Up => a.connection.upload.cmp(&b.connection.upload),
+            Self::DownSpeed => a.download.cmp(&b.download),
+            Self::UpSpeed => a.upload.cmp(&b.upload),
+            Self::Chains => a.connection.chains.cmp(&b.connection.chains),
+            Self::Rule => a
+                .connection
+                .rule
+                .cmp(&b.connection.rule)
+                .then_with(|| a.connection.rule_payload.cmp(&b.connection.rule_payload)),
+            Self::Time => a.connection.start.cmp(&b.connection.start),
+            Self::Src => (&a_meta.source_ip, &a_meta.source_port)
+                .cmp(&(&b_meta.source_ip, &b_meta.source_port)),
+            Self::Dest => (&a_meta.destination_ip, &a_meta.destination_port)
+                .cmp(&(&b_meta.destination_ip, &b_meta.destination_port)),
+            Self::Type => a_meta.connection_type.cmp(&b_meta.connection_type),
+        }
     }
 }
 
 impl EndlessSelf for ConSortBy {
     fn next_self(&mut self) {
-        todo!()
+        *self = Self::iter()
+            .cycle()
+            .skip_while(|x| x != self)
+            .nth(1)
+            .unwrap()
     }
 
     fn prev_self(&mut self) {
-        todo!()
+        *self = Self::iter()
+            .rev()
+            .cycle()
+            .skip_while(|x| x != self)
+            .nth(1)
+            .unwrap()
     }
 }
 
@@ -55,18 +91,73 @@ pub struct ConSort {
     order: SortOrder,
 }
 
+impl ConSort {
+    #[inline]
+    pub fn new(by: ConSortBy, order: SortOrder) -> Self {
+        Self { by, order }
+    }
+
+    #[inline]
+    pub fn by(&self) -> ConSortBy {
+        self.by
+    }
+
+    #[inline]
+    pub fn order(&self) -> SortOrder {
+        self.order
+    }
+}
+
 impl EndlessSelf for ConSort {
     fn next_self(&mut self) {
-        todo!()
+        match self.order {
+            SortOrder::Ascendant => self.order = SortOrder::Descendant,
+            SortOrder::Descendant => {
+                self.order = SortOrder::Ascendant;
+                self.by.next_self()
+            }
+        }
     }
 
     fn prev_self(&mut self) {
-        todo!()
+        match self.order {
+            SortOrder::Descendant => self.order = SortOrder::Ascendant,
+            SortOrder::Ascendant => {
+                self.order = SortOrder::Descendant;
+                self.by.prev_self()
+            }
+        }
+    }
+}
+
+impl Display for ConSort {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {}",
+            self.by,
+            match self.order {
+                SortOrder::Ascendant => "▲",
+                SortOrder::Descendant => "▼",
+            }
+        )
     }
 }
 
 impl SortMethod<ConnectionWithSpeed> for ConSort {
-    fn sort_fn(&self, a: &ConnectionWithSpeed, b: &ConnectionWithSpeed) -> std::cmp::Ordering {
+    fn sort_fn(&self, a: &ConnectionWithSpeed, b: &ConnectionWithSpeed) -> Ordering {
         self.by.sort_fn(a, b).order_by(self.order)
     }
 }
+
+#[test]
+fn test_con_sort_cycle() {
+    let mut sort = ConSort::new(ConSortBy::Type, SortOrder::Ascendant);
+    sort.next_self();
+    assert_eq!(sort, ConSort::new(ConSortBy::Type, SortOrder::Descendant));
+    sort.next_self();
+    assert_eq!(sort, ConSort::new(ConSortBy::Host, SortOrder::Ascendant));
+    sort.prev_self();
+    sort.prev_self();
+    assert_eq!(sort, ConSort::new(ConSortBy::Type, SortOrder::Ascendant));
+}
@@ -3,7 +3,7 @@ use std::cmp::Ordering;
 use clashctl_core::{model::Proxy, strum};
 use serde::{Deserialize, Serialize};
 
-use crate::{EndlessSelf, SortMethod, SortOrder};
+use crate::{DelayStats, EndlessSelf, SortMethod, SortOrder};
 
 #[derive(
     Debug,
@@ -209,6 +209,27 @@ impl SortMethod<(&String, &Proxy)> for ProxySort {
     }
 }
 
+impl SortMethod<DelayStats> for ProxySort {
+    fn sort_fn(&self, a: &DelayStats, b: &DelayStats) -> Ordering {
+        let ret = match self.by() {
+            ProxySortBy::Type => a.proxy_type.cmp(&b.proxy_type),
+            ProxySortBy::Name => a.name.cmp(&b.name),
+            ProxySortBy::Delay => match (a.avg, b.avg) {
+                (Some(a), Some(b)) => a.cmp(&b),
+                // Proxies that failed every round go to the end of list in
+                // either order, so they are not reversed
+                (Some(_), None) => return Ordering::Less,
+                (None, Some(_)) => return Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            },
+        };
+        match self.order() {
+            SortOrder::Ascendant => ret,
+            SortOrder::Descendant => ret.reverse(),
+        }
+    }
+}
+
 #[test]
 fn test() {
     let serialized = r#"ProxySort ( by: name, order: ascendant )"#;
@@ -221,3 +242,21 @@ fn test() {
         deserialized
     );
 }
+
+#[test]
+fn test_failed_last() {
+    use clashctl_core::model::ProxyType;
+
+    let stats = [Some(100), None, Some(50)]
+        .iter()
+        .enumerate()
+        .map(|(i, delay)| DelayStats::from_samples(&i.to_string(), ProxyType::Direct, &[*delay]))
+        .collect::<Vec<_>>();
+    let sorted = |sort: ProxySort| {
+        let mut stats = stats.clone();
+        stats.sort_by(|a, b| sort.sort_fn(a, b));
+        stats.into_iter().map(|x| x.name).collect::<Vec<_>>()
+    };
+    assert_eq!(sorted(ProxySort::by_delay_asc()), ["2", "0", "1"]);
+    assert_eq!(sorted(ProxySort::by_delay_dsc()), ["0", "2", "1"]);
+}
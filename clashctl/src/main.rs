@@ -1,13 +1,40 @@
-mod_use::mod_use![command, proxy_render, utils, error, interactive, ui];
+mod_use::mod_use![
+    command,
+    delay,
+    matcher,
+    output,
+    proxy_render,
+    rotate,
+    stream,
+    utils,
+    error,
+    interactive,
+    ui
+];
 
 pub use clap;
 use log::debug;
 use ui::main_loop;
 
-use crate::{clap::Parser, Cmd, Opts};
+use crate::{
+    clap::{CommandFactory, FromArgMatches},
+    Cmd, CompleteArg, Opts,
+};
 
 pub fn run() {
-    let opts = Opts::parse();
+    // `__complete` is added here instead of to `Opts`, as clap_complete puts
+    // hidden subcommands into completion scripts too
+    let matches = Opts::command()
+        .subcommand(CompleteArg::command().name("__complete").hide(true))
+        .get_matches();
+    if let Some(("__complete", matches)) = matches.subcommand() {
+        return match CompleteArg::from_arg_matches(matches) {
+            Ok(arg) => arg.handle(),
+            Err(e) => e.exit(),
+        };
+    }
+
+    let opts = Opts::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
     opts.init_logger();
     debug!("Opts: {:#?}", opts);
 
@@ -16,9 +43,20 @@ pub fn run() {
         Some(Cmd::Tui(opt)) => main_loop(opt, opts.flag).map_err(Into::into),
         Some(Cmd::Proxy(sub)) => sub.handle(&opts.flag),
         Some(Cmd::Server(sub)) => sub.handle(&opts.flag),
+        Some(Cmd::Preset(sub)) => sub.handle(&opts.flag),
+        Some(Cmd::Rule(sub)) => sub.handle(&opts.flag),
+        Some(Cmd::Conn(sub)) => sub.handle(&opts.flag),
+        Some(Cmd::Config(sub)) => sub.handle(&opts.flag),
+        Some(Cmd::Mode(opt)) => opt.handle(&opts.flag),
+        Some(Cmd::Log(opt)) => opt.handle(&opts.flag),
+        Some(Cmd::Traffic(opt)) => opt.handle(&opts.flag),
+        Some(Cmd::Auto(opt)) => opt.handle(&opts.flag),
+        Some(Cmd::Doctor) => doctor(&opts.flag),
+        Some(Cmd::Sources) => sources(&opts.flag),
         Some(Cmd::Completion(arg)) => arg.handle(),
     } {
-        eprintln!("{}", e)
+        eprintln!("{}", e);
+        std::process::exit(1)
     }
 }
 
@@ -0,0 +1,60 @@
+use crate::{Error, Result};
+
+/// Find the one candidate that `name` refers to. Candidates are matched in
+/// tiers, and the first tier with any match decides:
+///
+/// 1. Exact match
+/// 2. Case-insensitive match
+/// 3. Case-insensitive substring
+/// 4. Case-insensitive subsequence (fuzzy), e.g. `hk1` for `HK-1`
+///
+/// Errors when no candidate matches, or when the deciding tier has more than
+/// one match.
+pub fn match_name<'a, I>(kind: &'static str, name: &str, candidates: I) -> Result<&'a str>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let candidates = candidates.into_iter().collect::<Vec<_>>();
+    let lower = name.to_lowercase();
+
+    let tiers: [&dyn Fn(&str) -> bool; 4] = [
+        &|x| x == name,
+        &|x| x.to_lowercase() == lower,
+        &|x| x.to_lowercase().contains(&lower),
+        &|x| is_subsequence(&lower, &x.to_lowercase()),
+    ];
+
+    for tier in tiers {
+        let mut matched = candidates.iter().filter(|x| tier(x)).copied();
+        match (matched.next(), matched.next()) {
+            (None, _) => continue,
+            (Some(one), None) => return Ok(one),
+            (Some(a), Some(b)) => {
+                let mut all = [a, b].into_iter().chain(matched).collect::<Vec<_>>();
+                all.sort_unstable();
+                return Err(Error::ambiguous(kind, name, all));
+            }
+        }
+    }
+
+    Err(Error::not_found(kind, name))
+}
+
+fn is_subsequence(needle: &str, haystack: &str) -> bool {
+    let mut haystack = haystack.chars();
+    needle.chars().all(|c| haystack.any(|x| x == c))
+}
+
+#[test]
+fn test_match_name() {
+    let names = ["HK-1", "HK-2", "hk-1", "JP-Tokyo", "US West"];
+    let find = |name| match_name("proxy", name, names);
+
+    assert_eq!(find("HK-1").unwrap(), "HK-1");
+    assert_eq!(find("jp-tokyo").unwrap(), "JP-Tokyo");
+    assert_eq!(find("west").unwrap(), "US West");
+    assert_eq!(find("jptk").unwrap(), "JP-Tokyo");
+    assert!(find("Hk-1").is_err());
+    assert!(find("hk").is_err());
+    assert!(find("nothing").is_err());
+}
@@ -0,0 +1,68 @@
+use std::io::{self, Write};
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{ErrorKind, Result};
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Default,
+    strum::EnumString,
+    strum::Display,
+    strum::EnumVariantNames,
+)]
+#[strum(ascii_case_insensitive, serialize_all = "lowercase")]
+pub enum OutputFormat {
+    #[default]
+    Table,
+    Json,
+    Yaml,
+    Jsonl,
+}
+
+/// Result of a command that can be printed in every [`OutputFormat`]
+pub trait Render: Serialize {
+    /// Print in human-readable form, used by [`OutputFormat::Table`]
+    fn render_table(&self);
+}
+
+impl OutputFormat {
+    #[inline]
+    pub fn is_table(&self) -> bool {
+        matches!(self, Self::Table)
+    }
+
+    /// Print `item` to stdout in this format. With `jsonl`, each element of a
+    /// list is printed on its own line.
+    ///
+    /// Machine-readable output is often piped into tools like `head`, so the
+    /// process exits quietly once stdout is closed.
+    pub fn print<T: Render + ?Sized>(&self, item: &T) -> Result<()> {
+        let out = match self {
+            Self::Table => {
+                item.render_table();
+                return Ok(());
+            }
+            Self::Json => serde_json::to_string_pretty(item)? + "\n",
+            Self::Yaml => serde_yaml::to_string(item)?,
+            Self::Jsonl => match serde_json::to_value(item)? {
+                Value::Array(items) => items.iter().map(|x| format!("{}\n", x)).collect(),
+                value => format!("{}\n", value),
+            },
+        };
+        let mut stdout = io::stdout().lock();
+        match stdout
+            .write_all(out.as_bytes())
+            .and_then(|_| stdout.flush())
+        {
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => std::process::exit(0),
+            res => res.map_err(ErrorKind::OutputError)?,
+        }
+        Ok(())
+    }
+}
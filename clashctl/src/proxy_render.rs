@@ -1,41 +1,71 @@
-use clashctl_core::model::{Proxies, Proxy};
+use clashctl_core::model::{Proxies, Proxy, ProxyType};
 use either::Either;
 use owo_colors::OwoColorize;
+use serde::Serialize;
 use terminal_size::{terminal_size, Height, Width};
 
 use crate::{
     interactive::{ProxySort, Sortable},
-    ProxyListOpt,
+    ProxyListOpt, Render,
 };
 
-pub trait RenderList {
-    fn render_list(&self, opt: &ProxyListOpt);
-    fn render_plain(&self, opt: &ProxyListOpt);
-    fn render_tree(&self, opt: &ProxyListOpt);
+#[derive(Serialize, Debug, Clone)]
+pub struct ProxyEntry {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub proxy_type: ProxyType,
+    /// Latest delay in ms, 0 means the latest test failed
+    pub delay: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub now: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub members: Option<Vec<ProxyEntry>>,
 }
 
-impl RenderList for Proxies {
-    // pub fn names(&self) -> impl Iterator<Item = &String> {
-    //     self.iter().map(|x| x.0)
-    // }
+impl ProxyEntry {
+    pub fn new(name: &str, proxy: &Proxy) -> Self {
+        Self {
+            name: name.to_owned(),
+            proxy_type: proxy.proxy_type,
+            delay: proxy.history.first().map(|x| x.delay),
+            now: proxy.now.to_owned(),
+            members: None,
+        }
+    }
 
-    fn render_list(&self, opt: &ProxyListOpt) {
-        let (Width(terminal_width), _) = terminal_size().unwrap_or((Width(70), Height(0)));
-        println!("\n{:-<1$}", "", terminal_width as usize);
-        println!("{:<18}{:<8}NAME", "TYPE", "DELAY");
-        println!("{:-<1$}", "", terminal_width as usize);
+    pub fn delay_str(&self) -> String {
+        match self.delay {
+            Some(0) => "?".to_owned(),
+            Some(delay) => delay.to_string(),
+            None => "-".to_owned(),
+        }
+    }
+}
+
+/// Proxies listed by `proxy list`, either plain or grouped
+#[derive(Serialize, Debug, Clone)]
+#[serde(transparent)]
+pub struct ProxyList {
+    entries: Vec<ProxyEntry>,
+    #[serde(skip)]
+    plain: bool,
+}
 
-        if opt.plain {
-            self.render_plain(opt)
+impl ProxyList {
+    pub fn new(proxies: &Proxies, opt: &ProxyListOpt) -> Self {
+        let entries = if opt.plain {
+            Self::plain(proxies, opt)
         } else {
-            self.render_tree(opt)
+            Self::tree(proxies, opt)
+        };
+        Self {
+            entries,
+            plain: opt.plain,
         }
-
-        println!("{:-<1$}", "", terminal_width as usize);
     }
 
-    fn render_plain(&self, opt: &ProxyListOpt) {
-        let mut list = self.iter().collect::<Vec<_>>();
+    fn plain(proxies: &Proxies, opt: &ProxyListOpt) -> Vec<ProxyEntry> {
+        let mut list = proxies.iter().collect::<Vec<_>>();
         let sort_method = ProxySort::new(opt.sort_by, opt.sort_order);
 
         list.sort_with(&sort_method);
@@ -56,22 +86,12 @@ impl RenderList for Proxies {
             }
         });
 
-        for (name, proxy) in iter {
-            let delay = proxy
-                .history
-                .get(0)
-                .map(|x| match x.delay {
-                    0 => "?".to_owned(),
-                    delay => delay.to_string(),
-                })
-                .unwrap_or_else(|| "-".into());
-            let type_name = proxy.proxy_type.to_string();
-            println!("{:<18}{:<8}{}", type_name.green(), delay, name)
-        }
+        iter.map(|(name, proxy)| ProxyEntry::new(name, proxy))
+            .collect()
     }
 
-    fn render_tree(&self, opt: &ProxyListOpt) {
-        let list = self
+    fn tree(proxies: &Proxies, opt: &ProxyListOpt) -> Vec<ProxyEntry> {
+        let list = proxies
             .iter()
             .filter(|x| {
                 let proxy_type = x.1.proxy_type;
@@ -87,37 +107,70 @@ impl RenderList for Proxies {
 
         let sort_method = ProxySort::new(opt.sort_by, opt.sort_order);
 
-        for (name, group) in groups.into_iter() {
-            // Since list only contains groups, and only groups have `all`, so it is safe to
-            // [`unwrap`]
-            println!("{:<16}  -       {}\n", group.proxy_type.blue(), name);
-            let mut members = group
-                .all
-                .as_ref()
-                .expect("Proxy groups should have `all`")
-                .iter()
-                .map(|member_name| self.iter().find(|(name, _)| &member_name == name).unwrap())
-                .collect::<Vec<_>>();
-            members.sort_with(&sort_method);
-            for (
-                name,
-                Proxy {
-                    proxy_type,
-                    history,
-                    ..
-                },
-            ) in members
-            {
-                let delay = history
-                    .get(0)
-                    .map(|x| match x.delay {
-                        0 => "?".to_owned(),
-                        delay => delay.to_string(),
+        groups
+            .map(|(name, group)| {
+                // Since list only contains groups, and only groups have `all`, so it is safe
+                // to [`unwrap`]
+                let mut members = group
+                    .all
+                    .as_ref()
+                    .expect("Proxy groups should have `all`")
+                    .iter()
+                    .map(|member_name| {
+                        proxies
+                            .iter()
+                            .find(|(name, _)| &member_name == name)
+                            .unwrap()
                     })
-                    .unwrap_or_else(|| "-".into());
-                println!("  {:<16}{:<8}{}", proxy_type.green(), delay, name)
+                    .collect::<Vec<_>>();
+                members.sort_with(&sort_method);
+
+                ProxyEntry {
+                    members: Some(
+                        members
+                            .into_iter()
+                            .map(|(name, proxy)| ProxyEntry::new(name, proxy))
+                            .collect(),
+                    ),
+                    ..ProxyEntry::new(name, group)
+                }
+            })
+            .collect()
+    }
+}
+
+impl Render for ProxyList {
+    fn render_table(&self) {
+        let (Width(terminal_width), _) = terminal_size().unwrap_or((Width(70), Height(0)));
+        println!("\n{:-<1$}", "", terminal_width as usize);
+        println!("{:<18}{:<8}NAME", "TYPE", "DELAY");
+        println!("{:-<1$}", "", terminal_width as usize);
+
+        if self.plain {
+            for entry in &self.entries {
+                let type_name = entry.proxy_type.to_string();
+                println!(
+                    "{:<18}{:<8}{}",
+                    type_name.green(),
+                    entry.delay_str(),
+                    entry.name
+                )
+            }
+        } else {
+            for group in &self.entries {
+                println!("{:<16}  -       {}\n", group.proxy_type.blue(), group.name);
+                for member in group.members.iter().flatten() {
+                    println!(
+                        "  {:<16}{:<8}{}",
+                        member.proxy_type.green(),
+                        member.delay_str(),
+                        member.name
+                    )
+                }
+                println!();
             }
-            println!();
         }
+
+        println!("{:-<1$}", "", terminal_width as usize);
     }
 }
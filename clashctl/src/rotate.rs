@@ -0,0 +1,99 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::PathBuf,
+};
+
+/// A file that is rotated once it grows beyond `max_size`. Rotated files are
+/// renamed to `<path>.1`, `<path>.2` and so on, and at most `keep` of them
+/// are kept. Files are only rotated at the start of a line.
+#[derive(Debug)]
+pub struct RotatingFile {
+    path: PathBuf,
+    max_size: u64,
+    keep: usize,
+    file: File,
+    size: u64,
+    line_start: bool,
+}
+
+impl RotatingFile {
+    pub fn open(path: impl Into<PathBuf>, max_size: u64, keep: usize) -> io::Result<Self> {
+        let path = path.into();
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            path,
+            max_size,
+            keep,
+            file,
+            size,
+            line_start: true,
+        })
+    }
+
+    fn rotated(&self, index: usize) -> PathBuf {
+        let mut name = self.path.clone().into_os_string();
+        name.push(format!(".{}", index));
+        name.into()
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        if self.keep == 0 {
+            self.file = File::create(&self.path)?;
+        } else {
+            for index in (1..self.keep).rev() {
+                let from = self.rotated(index);
+                if from.exists() {
+                    fs::rename(from, self.rotated(index + 1))?;
+                }
+            }
+            fs::rename(&self.path, self.rotated(1))?;
+            self.file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)?;
+        }
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.line_start && self.size > 0 && self.size + buf.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        if written > 0 {
+            self.line_start = buf[written - 1] == b'\n';
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+#[test]
+fn test_rotating_file() {
+    let dir = std::env::temp_dir().join(format!("clashctl-rotate-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("clash.log");
+
+    let mut file = RotatingFile::open(&path, 10, 2).unwrap();
+    for line in ["aaaaaaaa", "bbbbbbbb", "cccccccc", "dddddddd"] {
+        writeln!(file, "{}", line).unwrap();
+    }
+    file.flush().unwrap();
+
+    assert_eq!(fs::read_to_string(&path).unwrap(), "dddddddd\n");
+    assert_eq!(fs::read_to_string(file.rotated(1)).unwrap(), "cccccccc\n");
+    assert_eq!(fs::read_to_string(file.rotated(2)).unwrap(), "bbbbbbbb\n");
+    assert!(!file.rotated(3).exists());
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,46 @@
+use std::{
+    sync::mpsc::{self, Receiver},
+    thread,
+};
+
+use clashctl_core::LongHaul;
+use serde::de::DeserializeOwned;
+
+use crate::Result;
+
+pub enum StreamEvent<T> {
+    Item(T),
+    /// Stream is closed by server, or broken by the error
+    Closed(Option<clashctl_core::Error>),
+    /// Ctrl-C is pressed
+    Interrupted,
+}
+
+/// Read `stream` in another thread, as reading blocks until the next item
+/// arrives. Items are sent to the returned receiver, along with Ctrl-C
+/// presses so that commands can stop at any time and exit cleanly.
+pub fn spawn_stream<T>(stream: LongHaul<T>) -> Result<Receiver<StreamEvent<T>>>
+where
+    T: DeserializeOwned + Send + 'static,
+{
+    let (tx, rx) = mpsc::channel();
+    let interrupt = tx.clone();
+    ctrlc::set_handler(move || {
+        let _ = interrupt.send(StreamEvent::Interrupted);
+    })?;
+
+    thread::spawn(move || {
+        for item in stream {
+            let event = match item {
+                Ok(item) => StreamEvent::Item(item),
+                Err(e) => StreamEvent::Closed(Some(e)),
+            };
+            if tx.send(event).is_err() {
+                return;
+            }
+        }
+        let _ = tx.send(StreamEvent::Closed(None));
+    });
+
+    Ok(rx)
+}
@@ -1,5 +1,7 @@
 #[derive(Debug, Clone)]
 pub enum Action {
-    TestLatency { proxies: Vec<String> },
+    TestLatency { group: String, proxies: Vec<String> },
     ApplySelection { group: String, proxy: String },
+    InsertRule { rule: String },
+    ApplyPreset { name: String },
 }
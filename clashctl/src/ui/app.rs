@@ -56,7 +56,7 @@ fn wrap_up(mut terminal: Terminal<Backend>) -> TuiResult<()> {
 
 pub fn main_loop(opt: TuiOpt, flag: Flags) -> TuiResult<()> {
     let config = flag.get_config()?;
-    if config.using_server().is_none() {
+    if flag.get_server()?.is_none() {
         println!(
             "{} No API server configured yet. Use this command to add a server:\n\n  $ {}",
             "WARN:".red(),
@@ -2,7 +2,9 @@ mod_use::mod_use![
     block_footer,
     constants,
     movable_list,
+    preset,
     proxy,
+    rule_confirm,
     sparkline,
     tabs,
     traffic
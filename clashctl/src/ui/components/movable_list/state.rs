@@ -91,6 +91,15 @@ where
         self.sort();
     }
 
+    /// Item on top of the visible part of list, only when the list is held
+    pub fn current_item(&self) -> Option<&T> {
+        if !self.offset.hold {
+            return None;
+        }
+        let y = self.offset.y.min(self.items.len().saturating_sub(1));
+        self.items.iter().rev().nth(y)
+    }
+
     pub fn push(&mut self, item: T) {
         self.items.push(item);
         if self.offset.hold {
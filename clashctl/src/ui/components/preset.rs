@@ -0,0 +1,102 @@
+use crossterm::event::KeyCode;
+use tui::{
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Clear, Paragraph, Widget},
+};
+
+use crate::ui::{get_focused_block, Action, ListEvent};
+
+/// Popup on the proxy page for choosing a saved preset to apply
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PresetPicker {
+    names: Vec<String>,
+    cursor: usize,
+}
+
+impl PresetPicker {
+    pub fn new(names: Vec<String>) -> Self {
+        Self { names, cursor: 0 }
+    }
+
+    pub fn handle(&mut self, event: ListEvent) -> Option<Action> {
+        let step = if event.fast { 3 } else { 1 };
+        match event.code {
+            KeyCode::Up => self.cursor = self.cursor.saturating_sub(step),
+            KeyCode::Down => {
+                self.cursor = (self.cursor + step).min(self.names.len().saturating_sub(1))
+            }
+            KeyCode::Right | KeyCode::Enter => {
+                return self.names.get(self.cursor).map(|name| Action::ApplyPreset {
+                    name: name.to_owned(),
+                })
+            }
+            _ => {}
+        }
+        None
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PresetPickerWidget<'a> {
+    state: &'a PresetPicker,
+}
+
+impl<'a> PresetPickerWidget<'a> {
+    pub fn new(state: &'a PresetPicker) -> Self {
+        Self { state }
+    }
+}
+
+impl<'a> Widget for PresetPickerWidget<'a> {
+    fn render(self, area: Rect, buf: &mut tui::buffer::Buffer) {
+        let width = self
+            .state
+            .names
+            .iter()
+            .map(|x| x.chars().count())
+            .max()
+            .unwrap_or_default()
+            .max(24) as u16
+            + 6;
+        let height = self.state.names.len() as u16 + 2;
+        let popup = Rect {
+            x: area.x + area.width.saturating_sub(width) / 2,
+            y: area.y + area.height.saturating_sub(height) / 2,
+            width: width.min(area.width),
+            height: height.min(area.height),
+        };
+
+        // Keep the cursor in view when there are more presets than rows
+        let rows = popup.height.saturating_sub(2) as usize;
+        let skip = (self.state.cursor + 1).saturating_sub(rows);
+        let text = self
+            .state
+            .names
+            .iter()
+            .enumerate()
+            .skip(skip)
+            .map(|(i, name)| {
+                if i == self.state.cursor {
+                    Spans::from(Span::styled(
+                        format!(" ▶ {}", name),
+                        Style::default()
+                            .fg(Color::Green)
+                            .add_modifier(Modifier::BOLD),
+                    ))
+                } else {
+                    Spans::from(Span::styled(
+                        format!("   {}", name),
+                        Style::default().fg(Color::White),
+                    ))
+                }
+            })
+            .collect::<Vec<_>>();
+
+        Clear.render(popup, buf);
+        Paragraph::new(text)
+            .block(get_focused_block("Presets"))
+            .render(popup, buf);
+    }
+}
@@ -114,6 +114,7 @@ impl<'a> ProxyTree<'a> {
                     FooterItem::spans(help_footer("Test", style, highlight)).wrapped()
                 },
                 FooterItem::spans(sort),
+                FooterItem::spans(help_footer("Preset", style, highlight)).wrapped(),
             ];
 
             footer.append_left(&mut left);
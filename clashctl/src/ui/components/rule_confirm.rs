@@ -0,0 +1,54 @@
+use tui::{
+    layout::{Alignment, Rect},
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Clear, Paragraph, Widget, Wrap},
+};
+
+use crate::ui::get_focused_block;
+
+/// Popup on the connection page asking before a generated rule is written
+/// into the profile
+#[derive(Clone, Debug)]
+pub struct RuleConfirmWidget<'a> {
+    rule: &'a str,
+}
+
+impl<'a> RuleConfirmWidget<'a> {
+    pub fn new(rule: &'a str) -> Self {
+        Self { rule }
+    }
+}
+
+impl<'a> Widget for RuleConfirmWidget<'a> {
+    fn render(self, area: Rect, buf: &mut tui::buffer::Buffer) {
+        let width = (self.rule.chars().count().max(36) as u16 + 4).min(area.width);
+        let height = 4.min(area.height);
+        let popup = Rect {
+            x: area.x + area.width.saturating_sub(width) / 2,
+            y: area.y + area.height.saturating_sub(height) / 2,
+            width,
+            height,
+        };
+
+        let text = vec![
+            Spans::from(Span::styled(
+                self.rule,
+                Style::default()
+                    .fg(Color::Green)
+                    .add_modifier(Modifier::BOLD),
+            )),
+            Spans::from(Span::styled(
+                "[Enter] Insert  [Esc] Cancel",
+                Style::default().fg(Color::DarkGray),
+            )),
+        ];
+
+        Clear.render(popup, buf);
+        Paragraph::new(text)
+            .alignment(Alignment::Center)
+            .wrap(Wrap { trim: true })
+            .block(get_focused_block("Insert rule into profile?"))
+            .render(popup, buf);
+    }
+}
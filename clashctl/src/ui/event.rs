@@ -79,6 +79,8 @@ pub enum InputEvent {
     ToggleHold,
     List(ListEvent),
     TestLatency,
+    PickPreset,
+    GenerateRule { policy: &'static str },
     NextSort,
     PrevSort,
     Other(KE),
@@ -131,6 +133,9 @@ impl TryFrom<KC> for Event {
         match value {
             KC::Char('q') | KC::Char('x') => Ok(Event::Quit),
             KC::Char('t') => Ok(Event::Input(InputEvent::TestLatency)),
+            KC::Char('p') => Ok(Event::Input(InputEvent::PickPreset)),
+            KC::Char('r') => Ok(Event::Input(InputEvent::GenerateRule { policy: "REJECT" })),
+            KC::Char('d') => Ok(Event::Input(InputEvent::GenerateRule { policy: "DIRECT" })),
             KC::Esc => Ok(Event::Input(InputEvent::Esc)),
             KC::Char(' ') => Ok(Event::Input(InputEvent::ToggleHold)),
             KC::Char(char) if char.is_ascii_digit() => Ok(Event::Input(InputEvent::TabGoto(
@@ -7,7 +7,7 @@ use tui::{
 };
 
 use crate::{
-    components::{MovableList, MovableListItem},
+    components::{MovableList, MovableListItem, RuleConfirmWidget},
     define_widget,
     interactive::clashctl::model::ConnectionWithSpeed,
     HMS,
@@ -18,6 +18,9 @@ define_widget!(ConnectionPage);
 impl<'a> Widget for ConnectionPage<'a> {
     fn render(self, area: tui::layout::Rect, buf: &mut tui::buffer::Buffer) {
         MovableList::new("Connections", &self.state.con_state).render(area, buf);
+        if let Some(ref rule) = self.state.pending_rule {
+            RuleConfirmWidget::new(rule).render(area, buf);
+        }
     }
 }
 
@@ -1,11 +1,17 @@
 use tui::widgets::Widget;
 
-use crate::{components::ProxyTreeWidget, define_widget};
+use crate::{
+    components::{PresetPickerWidget, ProxyTreeWidget},
+    define_widget,
+};
 
 define_widget!(ProxyPage);
 
 impl<'a> Widget for ProxyPage<'a> {
     fn render(self, area: tui::layout::Rect, buf: &mut tui::buffer::Buffer) {
         ProxyTreeWidget::new(&self.state.proxy_tree).render(area, buf);
+        if let Some(ref picker) = self.state.preset_picker {
+            PresetPickerWidget::new(picker).render(area, buf);
+        }
     }
 }
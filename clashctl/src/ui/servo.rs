@@ -4,15 +4,18 @@ use std::{
     time::Duration,
 };
 
-use clashctl_core::Clash;
+use clashctl_core::{Clash, RulePosition};
 use crossterm::event::Event as CrossTermEvent;
 use log::warn;
 use rayon::prelude::*;
 
 use crate::{
-    interactive::Flags,
+    interactive::{
+        apply_preset, insert_rule_into_profile, Flags, InteractiveError, PresetAction, Server,
+    },
     ui::{
         event::{Event, UpdateEvent},
+        get_config,
         utils::{Interval, Pulse},
         Action, TuiOpt, TuiResult,
     },
@@ -21,7 +24,15 @@ use crate::{
 pub type Job = JoinHandle<TuiResult<()>>;
 
 pub fn servo(tx: Sender<Event>, rx: Receiver<Action>, opt: TuiOpt, flags: Flags) -> TuiResult<()> {
-    let clash = flags.connect_server_from_config()?;
+    let server = flags
+        .get_server()?
+        .ok_or(InteractiveError::ServerNotFound)?;
+    // Latency tests are run by Clash, so requests have to wait for them
+    let timeout = flags.timeout(Some(&server))
+        + flags.max_test_timeout(Some(&server), &get_config().group_tests);
+    let clash = server
+        .clone()
+        .into_clash_with_timeout(Some(Duration::from_millis(timeout)))?;
     clash.get_version()?;
 
     scope(|r| -> TuiResult<()> {
@@ -35,9 +46,9 @@ pub fn servo(tx: Sender<Event>, rx: Receiver<Action>, opt: TuiOpt, flags: Flags)
         let handle3 = r.spawn(|| log_job(tx_clone, &clash));
 
         let tx_clone = tx.clone();
-        let handle4 = r.spawn(|| req_job(&opt, &flags, tx_clone, &clash));
+        let handle4 = r.spawn(|| req_job(&opt, &server, tx_clone, &clash));
 
-        let handle5 = r.spawn(|| action_job(&opt, &flags, tx, rx, &clash));
+        let handle5 = r.spawn(|| action_job(&opt, &flags, &server, tx, rx, &clash));
 
         handle1.join().unwrap()?;
         handle2.join().unwrap()?;
@@ -63,13 +74,21 @@ fn input_job(tx: Sender<Event>) -> TuiResult<()> {
     Ok(())
 }
 
-fn req_job(_opt: &TuiOpt, _flags: &Flags, tx: Sender<Event>, clash: &Clash) -> TuiResult<()> {
-    let mut interval = Interval::every(Duration::from_millis(50));
-    let mut connection_pulse = Pulse::new(20); // Every 1 s
-    let mut proxies_pulse = Pulse::new(100); //   Every 5 s + 0 tick
-    let mut rules_pulse = Pulse::new(101); //     Every 5 s + 1 tick
-    let mut version_pulse = Pulse::new(102); //   Every 5 s + 2 tick
-    let mut config_pulse = Pulse::new(103); //    Every 5 s + 3 tick
+fn req_job(_opt: &TuiOpt, server: &Server, tx: Sender<Event>, clash: &Clash) -> TuiResult<()> {
+    const TICK: u64 = 50;
+
+    let refresh = server.refresh.unwrap_or_default();
+    let ticks = |ms: u64| (ms / TICK).max(1);
+
+    let mut interval = Interval::every(Duration::from_millis(TICK));
+    // Periods come from `refresh` of the server. Rules, version and configs
+    // are polled 1, 2 and 3 ticks (50 ms each) less often than configured, so
+    // that requests with the same interval don't all fire on the same tick
+    let mut connection_pulse = Pulse::new(ticks(refresh.connections));
+    let mut proxies_pulse = Pulse::new(ticks(refresh.proxies));
+    let mut rules_pulse = Pulse::new(ticks(refresh.rules) + 1);
+    let mut version_pulse = Pulse::new(ticks(refresh.configs) + 2);
+    let mut config_pulse = Pulse::new(ticks(refresh.configs) + 3);
 
     loop {
         if version_pulse.tick() {
@@ -118,6 +137,7 @@ fn log_job(tx: Sender<Event>, clash: &Clash) -> TuiResult<()> {
 fn action_job(
     _opt: &TuiOpt,
     flags: &Flags,
+    server: &Server,
     tx: Sender<Event>,
     rx: Receiver<Action>,
     clash: &Clash,
@@ -125,12 +145,17 @@ fn action_job(
     while let Ok(action) = rx.recv() {
         tx.send(Event::Action(action.clone()))?;
         match action {
-            Action::TestLatency { proxies } => {
+            Action::TestLatency { group, proxies } => {
+                let config = get_config();
+                let group_test = config.group_test(&group);
+                let test_url = flags.test_url(Some(server), group_test);
+                let timeout = flags.test_timeout(Some(server), group_test);
+                drop(config);
                 let result = proxies
                     .par_iter()
                     .filter_map(|proxy| {
                         clash
-                            .get_proxy_delay(proxy, flags.test_url.as_str(), flags.timeout)
+                            .get_proxy_delay(proxy, test_url.as_str(), timeout)
                             .err()
                     })
                     .collect::<Vec<_>>();
@@ -153,9 +178,69 @@ fn action_job(
                 tx.send(Event::Update(UpdateEvent::Proxies(clash.get_proxies()?)))?;
             }
             Action::ApplySelection { group, proxy } => {
-                let _ = clash
-                    .set_proxygroup_selected(&group, &proxy)
-                    .map_err(|e| warn!("{:?}", e));
+                match server.check_writable() {
+                    Ok(_) => {
+                        let _ = clash
+                            .set_proxygroup_selected(&group, &proxy)
+                            .map_err(|e| warn!("{:?}", e));
+                    }
+                    Err(e) => warn!("Cannot select `{}` in `{}`: {}", proxy, group, e),
+                }
+                tx.send(Event::Update(UpdateEvent::Proxies(clash.get_proxies()?)))?;
+            }
+            Action::InsertRule { rule } => {
+                let profile = get_config().profile.clone();
+                match profile.path {
+                    Some(_) if profile.reload && server.read_only => warn!(
+                        "Cannot insert `{}`: {}",
+                        rule,
+                        InteractiveError::ReadOnlyServer(server.to_string())
+                    ),
+                    Some(ref path) => {
+                        let _ = insert_rule_into_profile(
+                            path,
+                            &rule,
+                            RulePosition::Top,
+                            profile.reload.then_some(clash),
+                        )
+                        .map_err(|e| warn!("{}", e));
+                        tx.send(Event::Update(UpdateEvent::Rules(clash.get_rules()?)))?;
+                    }
+                    None => warn!(
+                        "Cannot insert `{}`: {}",
+                        rule,
+                        InteractiveError::ProfileNotSet
+                    ),
+                }
+            }
+            Action::ApplyPreset { name } => {
+                let preset = match get_config().presets.get(&name) {
+                    Some(preset) => preset.to_owned(),
+                    None => {
+                        warn!("Cannot apply `{}`: preset not found", name);
+                        continue;
+                    }
+                };
+                match server
+                    .check_writable()
+                    .and_then(|_| apply_preset(clash, &preset))
+                {
+                    Ok(entries) => {
+                        for entry in entries {
+                            match entry.action {
+                                PresetAction::MissingGroup => {
+                                    warn!("Preset `{}`: no group named {}", name, entry.group)
+                                }
+                                PresetAction::MissingMember => warn!(
+                                    "Preset `{}`: {} is no longer in {}",
+                                    name, entry.proxy, entry.group
+                                ),
+                                _ => {}
+                            }
+                        }
+                    }
+                    Err(e) => warn!("Cannot apply `{}`: {}", name, e),
+                }
                 tx.send(Event::Update(UpdateEvent::Proxies(clash.get_proxies()?)))?;
             }
         }
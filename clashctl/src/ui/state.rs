@@ -1,15 +1,19 @@
 use std::{collections::HashMap, time::Instant};
 
-use clashctl_core::model::{ConnectionWithSpeed, Log, Rule, Traffic, Version};
+use clashctl_core::model::{ConnectionWithSpeed, Log, Rule, RuleType, Traffic, Version};
+use crossterm::event::KeyCode;
+use log::warn;
 use smart_default::SmartDefault;
 
 use crate::{
     interactive::{Noop, RuleSort},
     ui::{
-        components::{MovableListManage, MovableListManager, MovableListState, ProxyTree},
-        TuiResult,
+        components::{
+            MovableListManage, MovableListManager, MovableListState, PresetPicker, ProxyTree,
+        },
+        get_config, TuiResult,
     },
-    Action, ConfigState, Event, InputEvent, UpdateEvent,
+    Action, ConfigState, Event, InputEvent, ListEvent, UpdateEvent,
 };
 
 pub(crate) type LogListState<'a> = MovableListState<'a, Log, Noop>;
@@ -29,6 +33,10 @@ pub struct TuiStates<'a> {
     pub page_index: u8,
     pub show_debug: bool,
     pub proxy_tree: ProxyTree<'a>,
+    pub preset_picker: Option<PresetPicker>,
+    /// Rule generated from a connection, waiting to be confirmed before it's
+    /// written into the profile
+    pub pending_rule: Option<String>,
     pub rule_freq: HashMap<String, usize>,
     // (upload_size, download_size)
     pub con_size: (u64, u64),
@@ -127,6 +135,37 @@ impl<'a> TuiStates<'a> {
     }
 
     fn handle_input(&mut self, event: InputEvent) -> TuiResult<Option<Action>> {
+        // Preset picker takes over moving and confirming while it's open
+        if let Some(ref mut picker) = self.preset_picker {
+            match event {
+                InputEvent::Esc | InputEvent::PickPreset => {
+                    self.preset_picker = None;
+                    return Ok(None);
+                }
+                InputEvent::List(list_event) => {
+                    let action = picker.handle(list_event);
+                    if action.is_some() {
+                        self.preset_picker = None;
+                    }
+                    return Ok(action);
+                }
+                InputEvent::TabGoto(_) => self.preset_picker = None,
+                _ => return Ok(None),
+            }
+        }
+
+        // A generated rule is only inserted on Enter, any other key cancels it
+        if let Some(rule) = self.pending_rule.take() {
+            match event {
+                InputEvent::List(ListEvent {
+                    code: KeyCode::Enter,
+                    ..
+                }) => return Ok(Some(Action::InsertRule { rule })),
+                InputEvent::TabGoto(_) => {}
+                _ => return Ok(None),
+            }
+        }
+
         match event {
             InputEvent::TabGoto(index) => {
                 if index >= 1 && index <= self.page_len() as u8 {
@@ -167,7 +206,39 @@ impl<'a> TuiStates<'a> {
                         .filter(|x| x.proxy_type().is_normal())
                         .map(|x| x.name().into())
                         .collect();
-                    return Ok(Some(Action::TestLatency { proxies }));
+                    return Ok(Some(Action::TestLatency {
+                        group: group.name().to_owned(),
+                        proxies,
+                    }));
+                }
+            }
+            InputEvent::PickPreset => {
+                if self.title() == "Proxies" {
+                    let names = get_config().presets.keys().cloned().collect::<Vec<_>>();
+                    if names.is_empty() {
+                        warn!("No presets saved, use `clashctl preset save <name>` first");
+                    } else {
+                        self.preset_picker = Some(PresetPicker::new(names));
+                    }
+                }
+            }
+            InputEvent::GenerateRule { policy } => {
+                if self.title() == "Conns" {
+                    let meta = match self.con_state.current_item() {
+                        Some(item) => &item.connection.metadata,
+                        None => return Ok(None),
+                    };
+                    let rule_type = if meta.host.is_empty() {
+                        RuleType::IPCIDR
+                    } else {
+                        RuleType::DomainSuffix
+                    };
+                    if let Some(rule) = meta
+                        .to_rule(rule_type, policy, None)
+                        .and_then(|x| x.to_profile_line())
+                    {
+                        self.pending_rule = Some(rule);
+                    }
                 }
             }
             InputEvent::NextSort => {